@@ -0,0 +1,91 @@
+// Deserializes a document made of many siblings each nested just deep enough
+// to cross the red zone, with and without a segment pool.
+//
+//     cargo bench --bench segment_pool
+
+use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+use serde_stacker::Config;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SIBLINGS: usize = 20_000;
+const DEPTH: usize = 100;
+const ITERATIONS: usize = 5;
+
+fn main() {
+    let mut json = String::from("[");
+    for i in 0..SIBLINGS {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str(&"[".repeat(DEPTH));
+        json.push_str(&"]".repeat(DEPTH));
+    }
+    json.push(']');
+
+    for segment_pool in [0, 4] {
+        // The thread starts out inside the red zone, and each sibling crosses
+        // into it again partway down on the first segment.
+        let config = Config::builder()
+            .red_zone(96 * 1024)
+            .stack_size(128 * 1024)
+            .segment_pool(segment_pool)
+            .build()
+            .unwrap();
+        let json = json.as_str();
+        let elapsed = thread::scope(|scope| {
+            thread::Builder::new()
+                .stack_size(64 * 1024)
+                .spawn_scoped(scope, || run(json, config))
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+        println!(
+            "segment_pool({}): {:?} per iteration",
+            segment_pool,
+            elapsed / ITERATIONS as u32,
+        );
+    }
+}
+
+fn run(json: &str, config: Config) -> Duration {
+    let begin = Instant::now();
+    for _ in 0..ITERATIONS {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        deserializer.disable_recursion_limit();
+        let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+        Skip::deserialize(deserializer).unwrap();
+    }
+    begin.elapsed()
+}
+
+// Like IgnoredAny, but visits every level of nesting. Serde_json skips over
+// IgnoredAny without involving the visitor, so it would never grow the stack.
+struct Skip;
+
+impl<'de> Deserialize<'de> for Skip {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Skip)
+    }
+}
+
+impl<'de> Visitor<'de> for Skip {
+    type Value = Skip;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("array")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while let Some(Skip) = seq.next_element()? {}
+        Ok(Skip)
+    }
+}
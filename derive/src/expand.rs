@@ -0,0 +1,153 @@
+use crate::recursive;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_quote, Attribute, Data, DeriveInput, Error, Fields, GenericParam, Meta, Result, Token,
+    Visibility,
+};
+
+// Expands to a copy of the input type with only its serde attributes, plus
+// `#[serde(with = "serde_stacker::field")]` on every field whose type refers
+// back to the input type. Serde derives its impls as a remote definition for
+// the input type, and the input type's impls delegate to those.
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut shadow = input.clone();
+    shadow.ident = format_ident!("__StackSafe");
+    shadow.vis = Visibility::Inherited;
+    retain_serde_attrs(&mut shadow.attrs)?;
+    let remote = ident.to_string();
+    shadow.attrs.push(parse_quote!(#[serde(remote = #remote)]));
+
+    match &mut shadow.data {
+        Data::Struct(data) => annotate_fields(&mut data.fields, &input)?,
+        Data::Enum(data) => {
+            for variant in &mut data.variants {
+                retain_serde_attrs(&mut variant.attrs)?;
+                if has_with(&variant.attrs)? {
+                    for field in &mut variant.fields {
+                        retain_serde_attrs(&mut field.attrs)?;
+                    }
+                } else {
+                    annotate_fields(&mut variant.fields, &input)?;
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "StackSafe does not support unions",
+            ));
+        }
+    }
+
+    let mut ser_where = where_clause.cloned().unwrap_or_else(|| parse_quote!(where));
+    let mut de_where = ser_where.clone();
+    let mut de_generics = input.generics.clone();
+    de_generics.params.insert(0, parse_quote!('de));
+    for param in &input.generics.params {
+        match param {
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                ser_where
+                    .predicates
+                    .push(parse_quote!(#ident: ::serde::Serialize));
+                de_where
+                    .predicates
+                    .push(parse_quote!(#ident: ::serde::Deserialize<'de>));
+            }
+            GenericParam::Lifetime(param) => {
+                let lifetime = &param.lifetime;
+                de_where.predicates.push(parse_quote!('de: #lifetime));
+            }
+            GenericParam::Const(_) => {}
+        }
+    }
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+
+    Ok(quote! {
+        #[doc(hidden)]
+        const _: () = {
+            #[allow(dead_code)]
+            #[derive(::serde::Serialize, ::serde::Deserialize)]
+            #shadow
+
+            #[automatically_derived]
+            impl #impl_generics ::serde::Serialize for #ident #ty_generics #ser_where {
+                fn serialize<__S>(
+                    &self,
+                    __serializer: __S,
+                ) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: ::serde::Serializer,
+                {
+                    __StackSafe::serialize(self, __serializer)
+                }
+            }
+
+            #[automatically_derived]
+            impl #de_impl_generics ::serde::Deserialize<'de> for #ident #ty_generics #de_where {
+                fn deserialize<__D>(
+                    __deserializer: __D,
+                ) -> ::core::result::Result<Self, __D::Error>
+                where
+                    __D: ::serde::Deserializer<'de>,
+                {
+                    __StackSafe::deserialize(__deserializer)
+                }
+            }
+        };
+    })
+}
+
+fn annotate_fields(fields: &mut Fields, input: &DeriveInput) -> Result<()> {
+    for field in fields {
+        // Within the copy, `Self` would refer to the copy.
+        recursive::replace_self(&mut field.ty, input);
+        let forced = field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("stack_safe"));
+        retain_serde_attrs(&mut field.attrs)?;
+        if (forced || recursive::is_recursive(&field.ty, &input.ident)) && !has_with(&field.attrs)?
+        {
+            field
+                .attrs
+                .push(parse_quote!(#[serde(with = "::serde_stacker::field")]));
+        }
+    }
+    Ok(())
+}
+
+// Drops attributes other than #[serde(...)], after checking the ones meant
+// for this derive.
+fn retain_serde_attrs(attrs: &mut Vec<Attribute>) -> Result<()> {
+    for attr in attrs.iter() {
+        if attr.path().is_ident("stack_safe") {
+            attr.meta.require_path_only()?;
+        }
+    }
+    attrs.retain(|attr| attr.path().is_ident("serde"));
+    Ok(())
+}
+
+// Whether the user already customized how the field or variant is
+// serialized, in which case it is left alone.
+fn has_with(attrs: &[Attribute]) -> Result<bool> {
+    for attr in attrs {
+        let nested = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in nested {
+            let path = meta.path();
+            if path.is_ident("with")
+                || path.is_ident("serialize_with")
+                || path.is_ident("deserialize_with")
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,43 @@
+//! Derive macro for [`serde_stacker`], re-exported from there as
+//! `serde_stacker::StackSafe` when its `derive` feature is enabled.
+//!
+//! [`serde_stacker`]: https://docs.rs/serde_stacker
+
+#![allow(clippy::needless_pass_by_value)]
+
+mod expand;
+mod recursive;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Derive `Serialize` and `Deserialize` impls which grow the stack as needed
+/// at each level of recursion.
+///
+/// The impls are generated by serde's own derive, so the type can use all
+/// of the usual `#[serde(...)]` attributes, and the crate using this derive
+/// needs to depend on `serde` with its `derive` feature enabled. Do not also
+/// derive `Serialize` or `Deserialize` on the same type.
+///
+/// Every field whose type refers back to the type being derived, such as
+/// `Box<Self>`, `Vec<Expr>`, or `Option<Box<Node>>`, is serialized and
+/// deserialized through [`serde_stacker::field`][field], which checks whether
+/// the stack is running low and continues on a newly allocated stack if so.
+/// For recursion that goes through another type, mark the field that starts
+/// the cycle with `#[stack_safe]`. Fields that already have a
+/// `#[serde(with = ...)]` attribute or similar are left alone.
+///
+/// Only serialization and deserialization are covered. The type's `Drop`,
+/// `Debug`, `Clone` and `PartialEq` impls, whether derived or handwritten,
+/// still recurse once per level of nesting. `serde_stacker` provides `debug`,
+/// `eq` and `deep_clone` in place of the last three, while a deeply nested
+/// value needs to be taken apart iteratively before it is dropped.
+///
+/// [field]: https://docs.rs/serde_stacker/0.2/serde_stacker/field/index.html
+#[proc_macro_derive(StackSafe, attributes(serde, stack_safe))]
+pub fn derive_stack_safe(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
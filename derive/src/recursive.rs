@@ -0,0 +1,57 @@
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, DeriveInput, Ident, Path, Type};
+
+// Whether the type of a field mentions the type being derived, whether as
+// `Self` or by name, anywhere within it.
+pub fn is_recursive(ty: &Type, ident: &Ident) -> bool {
+    let mut visitor = FindSelf {
+        ident,
+        found: false,
+    };
+    visitor.visit_type(ty);
+    visitor.found
+}
+
+struct FindSelf<'a> {
+    ident: &'a Ident,
+    found: bool,
+}
+
+impl<'ast> Visit<'ast> for FindSelf<'_> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        for segment in &path.segments {
+            if segment.ident == "Self" || segment.ident == *self.ident {
+                self.found = true;
+                return;
+            }
+        }
+        visit::visit_path(self, path);
+    }
+}
+
+// Replaces `Self` within a field's type by the name of the type being derived.
+pub fn replace_self(ty: &mut Type, input: &DeriveInput) {
+    let ident = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut visitor = ReplaceSelf {
+        ty: parse_quote!(#ident #ty_generics),
+    };
+    visitor.visit_type_mut(ty);
+}
+
+struct ReplaceSelf {
+    ty: Type,
+}
+
+impl VisitMut for ReplaceSelf {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(path) = ty {
+            if path.qself.is_none() && path.path.is_ident("Self") {
+                *ty = self.ty.clone();
+                return;
+            }
+        }
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
@@ -0,0 +1,321 @@
+use alloc::string::ToString;
+use core::fmt::{self, Debug, Display};
+
+// Stack sizes are rounded to a multiple of the page size. Without std, or on
+// platforms where it cannot be queried, 4 KB is assumed.
+fn page_size() -> usize {
+    #[cfg(feature = "std")]
+    {
+        crate::stack::page_size()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        4 * 1024
+    }
+}
+
+// Without std the stack cannot grow, so the default configuration needs to
+// stop at a depth that a small stack can accommodate.
+#[cfg(not(feature = "std"))]
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Configuration of the [`Deserializer`][crate::Deserializer] and
+/// [`Serializer`][crate::Serializer] adapters.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_json::Value;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = serde_stacker::Config::builder()
+///     .red_zone(128 * 1024)
+///     .stack_size(4 * 1024 * 1024)
+///     .max_depth(10_000)
+///     .build()?;
+///
+/// let mut deserializer = serde_json::Deserializer::from_str("[[], [[]]]");
+/// let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+/// let value = Value::deserialize(deserializer)?;
+/// # let _ = value;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    red_zone: usize,
+    stack_size: usize,
+    max_depth: Option<usize>,
+    stack_budget: Option<usize>,
+    segment_pool: usize,
+    annotate_errors: bool,
+    track_path: bool,
+    thread_stack_size: usize,
+}
+
+impl Config {
+    /// Start building a configuration from the defaults.
+    #[must_use]
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Config::default(),
+        }
+    }
+
+    /// Minimum number of bytes of stack that must remain before entering a
+    /// level of nesting without growing the stack.
+    #[must_use]
+    pub fn red_zone(&self) -> usize {
+        self.red_zone
+    }
+
+    /// Size in bytes of each newly allocated stack segment.
+    #[must_use]
+    pub fn stack_size(&self) -> usize {
+        self.stack_size
+    }
+
+    /// Maximum number of nested sequences, maps, and enums, if limited.
+    #[must_use]
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Maximum number of bytes of allocated stack segments in use at once, if
+    /// limited.
+    #[must_use]
+    pub fn stack_budget(&self) -> Option<usize> {
+        self.stack_budget
+    }
+
+    /// Number of stack segments each thread keeps for reuse after the adapters
+    /// are done with them.
+    #[must_use]
+    pub fn segment_pool(&self) -> usize {
+        self.segment_pool
+    }
+
+    /// Whether deserialization errors are annotated with the nesting depth at
+    /// which they occurred.
+    #[must_use]
+    pub fn annotate_errors(&self) -> bool {
+        self.annotate_errors
+    }
+
+    /// Whether deserialization errors are annotated with the path to the
+    /// value at which they occurred.
+    #[must_use]
+    pub fn track_path(&self) -> bool {
+        self.track_path
+    }
+
+    /// Size in bytes of the stack of the thread spawned by the functions in
+    /// `serde_stacker::thread`.
+    #[must_use]
+    pub fn thread_stack_size(&self) -> usize {
+        self.thread_stack_size
+    }
+}
+
+impl Default for Config {
+    /// Reasonable default `red_zone` (64 KB) and `stack_size` (2 MB), no
+    /// `max_depth` or `stack_budget`, no `segment_pool`, no error annotation
+    /// or path tracking, and a `thread_stack_size` of 64 MB.
+    ///
+    /// Without the `std` feature, the default `max_depth` is 128 instead.
+    fn default() -> Self {
+        Config {
+            red_zone: 64 * 1024,
+            stack_size: 2 * 1024 * 1024,
+            #[cfg(feature = "std")]
+            max_depth: None,
+            #[cfg(not(feature = "std"))]
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            stack_budget: None,
+            segment_pool: 0,
+            annotate_errors: false,
+            track_path: false,
+            thread_stack_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Builder for a [`Config`].
+#[derive(Clone, Debug)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    /// At each level of nesting, the adapters check whether they are within
+    /// `red_zone` bytes of the end of the stack. If so, a new stack segment is
+    /// allocated on which to continue.
+    #[must_use]
+    pub fn red_zone(mut self, bytes: usize) -> Self {
+        self.config.red_zone = bytes;
+        self
+    }
+
+    /// Size of each new stack segment. This is rounded up to a multiple of the
+    /// system's page size, and must be larger than `red_zone`.
+    #[must_use]
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.config.stack_size = bytes;
+        self
+    }
+
+    /// Fail with an error instead of descending into a sequence, map, or enum
+    /// nested more than `max_depth` levels deep.
+    #[must_use]
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.config.max_depth = Some(depth);
+        self
+    }
+
+    /// Fail with an error instead of allocating a new stack segment when the
+    /// segments in use would add up to more than `stack_budget` bytes.
+    ///
+    /// The segments counted are those of every adapter on the current thread.
+    /// The budget also applies to adapters nested inside this one which have
+    /// no budget or a larger one, such as [`Stacked`][crate::Stacked] values,
+    /// the [`field`][crate::field] functions, or a separate adapter used by a
+    /// `Deserialize` impl to parse an embedded document.
+    #[must_use]
+    pub fn stack_budget(mut self, bytes: usize) -> Self {
+        self.config.stack_budget = Some(bytes);
+        self
+    }
+
+    /// Keep up to `count` stack segments per thread after use, instead of
+    /// unmapping each one as soon as the level of nesting that allocated it is
+    /// done. Input which crosses the red zone over and over at the same depth,
+    /// such as many siblings each nested just deep enough to need a new
+    /// segment, then reuses the same few segments rather than mapping and
+    /// unmapping one per sibling.
+    ///
+    /// Pooled segments stay mapped until the thread exits. Pooling is only
+    /// available on Unix platforms, and this setting is ignored elsewhere.
+    ///
+    /// `stacker` cannot be told about pooled segments, so a `Deserialize` or
+    /// `Serialize` impl running inside the adapters that calls
+    /// `stacker::maybe_grow` itself sees the limit of whichever stack
+    /// `stacker` last knew about. A pooled segment is only used where that
+    /// makes `stacker::remaining_stack()` report no more than the space
+    /// actually left on the segment, so such code grows early rather than
+    /// overflowing. Where no pooled or newly mapped segment qualifies, the
+    /// adapters fall back to `stacker::grow`, which `stacker` tracks itself.
+    /// `Stats::segments_reused` counts how often a pooled segment was used.
+    #[must_use]
+    pub fn segment_pool(mut self, count: usize) -> Self {
+        self.config.segment_pool = count;
+        self
+    }
+
+    /// Add the nesting depth at which a deserialization error occurred to the
+    /// error's message, as in `invalid type: string "x", expected u8 (at
+    /// nesting depth 3)`.
+    ///
+    /// The annotated error is constructed using `de::Error::custom`, so only
+    /// the message of the original error is preserved. Depending on the data
+    /// format, other details such as an error category may be lost. A
+    /// position at the end of the original message in the form `at line 1
+    /// column 13` is removed, because `serde_json` adds the position to the
+    /// annotated error again, as in `invalid type: string "x", expected u8
+    /// (at nesting depth 3) at line 1 column 14`.
+    ///
+    /// Without the `std` feature, this setting has no effect.
+    #[must_use]
+    pub fn annotate_errors(mut self, enable: bool) -> Self {
+        self.config.annotate_errors = enable;
+        self
+    }
+
+    /// Keep track of the location of the value being deserialized, in the
+    /// form of array indices, map keys, and enum variant names, and add it to
+    /// the message of any error, as in `invalid type: string "x", expected u8
+    /// (at path $.a[3].b[0])`.
+    ///
+    /// The path is kept on the heap, not the stack, so this works at any
+    /// depth. Like [`annotate_errors`][ConfigBuilder::annotate_errors], the
+    /// annotated error is constructed using `de::Error::custom`.
+    ///
+    /// Without the `std` feature, this setting has no effect.
+    #[must_use]
+    pub fn track_path(mut self, enable: bool) -> Self {
+        self.config.track_path = enable;
+        self
+    }
+
+    /// Size of the stack of the thread on which the functions in
+    /// `serde_stacker::thread` run the whole serialization or
+    /// deserialization. On platforms where [`is_growth_supported`] is false,
+    /// this is what determines how deeply nested the data can be.
+    ///
+    /// [`is_growth_supported`]: crate::is_growth_supported
+    #[must_use]
+    pub fn thread_stack_size(mut self, bytes: usize) -> Self {
+        self.config.thread_stack_size = bytes;
+        self
+    }
+
+    /// Validate the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `stack_size` overflows when rounded up to the page
+    /// size, or if `red_zone` is not smaller than `stack_size`.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let mut config = self.config;
+        let page_size = page_size();
+        config.stack_size = match config.stack_size.checked_add(page_size - 1) {
+            Some(stack_size) => stack_size / page_size * page_size,
+            None => {
+                return Err(ConfigError {
+                    kind: ErrorKind::StackSizeOverflow,
+                });
+            }
+        };
+        if config.red_zone >= config.stack_size {
+            return Err(ConfigError {
+                kind: ErrorKind::RedZoneTooLarge {
+                    red_zone: config.red_zone,
+                    stack_size: config.stack_size,
+                },
+            });
+        }
+        Ok(config)
+    }
+}
+
+/// Error returned by [`ConfigBuilder::build`] for an invalid configuration.
+pub struct ConfigError {
+    kind: ErrorKind,
+}
+
+enum ErrorKind {
+    StackSizeOverflow,
+    RedZoneTooLarge { red_zone: usize, stack_size: usize },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::StackSizeOverflow => formatter.write_str("stack_size is too large"),
+            ErrorKind::RedZoneTooLarge {
+                red_zone,
+                stack_size,
+            } => write!(
+                formatter,
+                "red_zone ({red_zone} bytes) must be smaller than stack_size ({stack_size} bytes)",
+            ),
+        }
+    }
+}
+
+impl Debug for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "ConfigError({:?})", self.to_string())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {}
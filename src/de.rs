@@ -1,30 +1,75 @@
+use crate::config::Config;
+#[cfg(feature = "std")]
+use crate::observer::GrowthObserver;
 use crate::param::Param;
+use crate::path::Segment;
+#[cfg(feature = "std")]
+use crate::stats::Stats;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
 use serde::de;
-use std::fmt;
 
 /// Deserializer adapter that avoids stack overflows by dynamically growing the
 /// stack.
 ///
 /// At each level of nested deserialization, the adapter will check whether it
 /// is within `red_zone` bytes of the end of the stack. If so, it will allocate
-/// a new stack of size `stack_size` on which to continue deserialization.
+/// a new stack of size `stack_size` on which to continue deserialization. See
+/// [`Config`] for these and other settings.
 pub struct Deserializer<D> {
     pub de: D,
-    pub red_zone: usize,
-    pub stack_size: usize,
+    param: Param,
 }
 
 impl<D> Deserializer<D> {
-    /// Build a deserializer adapter with reasonable default `red_zone` (64 KB)
-    /// and `stack_size` (2 MB).
+    /// Build a deserializer adapter with the default [`Config`].
     pub fn new(deserializer: D) -> Self {
-        let default_param = Param::default();
+        Self::with_config(deserializer, Config::default())
+    }
+
+    /// Build a deserializer adapter with the given configuration.
+    pub fn with_config(deserializer: D, config: Config) -> Self {
         Deserializer {
             de: deserializer,
-            red_zone: default_param.red_zone,
-            stack_size: default_param.stack_size,
+            param: Param::summarized(config, "deserialize"),
         }
     }
+
+    // Adapter for use inside of a Serialize or Deserialize impl, which may be
+    // running within an enclosing adapter already.
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn with_param(deserializer: D, param: Param) -> Self {
+        Deserializer {
+            de: deserializer,
+            param,
+        }
+    }
+
+    /// Record how deep the input nests and how much stack it uses into the
+    /// given [`Stats`].
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[must_use]
+    pub fn with_stats(mut self, stats: &Stats) -> Self {
+        self.param.stats = Some(stats.clone());
+        self
+    }
+
+    /// Notify the given [`GrowthObserver`] each time a new stack segment is
+    /// entered and exited.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[must_use]
+    pub fn with_observer<O>(mut self, observer: O) -> Self
+    where
+        O: GrowthObserver + 'static,
+    {
+        self.param.observer = Some(Arc::new(observer));
+        self
+    }
 }
 
 impl<'de, D> de::Deserializer<'de> for Deserializer<D>
@@ -37,168 +82,150 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_any(Visitor::new(visitor, param))
+        self.de.deserialize_any(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_bool(Visitor::new(visitor, param))
+        self.de.deserialize_bool(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_u8(Visitor::new(visitor, param))
+        self.de.deserialize_u8(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_u16(Visitor::new(visitor, param))
+        self.de.deserialize_u16(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_u32(Visitor::new(visitor, param))
+        self.de.deserialize_u32(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_u64(Visitor::new(visitor, param))
+        self.de.deserialize_u64(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_u128(Visitor::new(visitor, param))
+        self.de.deserialize_u128(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_i8(Visitor::new(visitor, param))
+        self.de.deserialize_i8(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_i16(Visitor::new(visitor, param))
+        self.de.deserialize_i16(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_i32(Visitor::new(visitor, param))
+        self.de.deserialize_i32(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_i64(Visitor::new(visitor, param))
+        self.de.deserialize_i64(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_i128(Visitor::new(visitor, param))
+        self.de.deserialize_i128(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_f32(Visitor::new(visitor, param))
+        self.de.deserialize_f32(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_f64(Visitor::new(visitor, param))
+        self.de.deserialize_f64(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_char(Visitor::new(visitor, param))
+        self.de.deserialize_char(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_str(Visitor::new(visitor, param))
+        self.de.deserialize_str(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_string(Visitor::new(visitor, param))
+        self.de
+            .deserialize_string(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_bytes(Visitor::new(visitor, param))
+        self.de.deserialize_bytes(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_byte_buf(Visitor::new(visitor, param))
+        self.de
+            .deserialize_byte_buf(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_option(Visitor::new(visitor, param))
+        self.de
+            .deserialize_option(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_unit(Visitor::new(visitor, param))
+        self.de.deserialize_unit(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_unit_struct<V>(
@@ -209,9 +236,8 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_unit_struct(name, Visitor::new(visitor, param))
+            .deserialize_unit_struct(name, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_newtype_struct<V>(
@@ -222,25 +248,23 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_newtype_struct(name, Visitor::new(visitor, param))
+            .deserialize_newtype_struct(name, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_seq(Visitor::new(visitor, param))
+        self.de.deserialize_seq(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_tuple(len, Visitor::new(visitor, param))
+        self.de
+            .deserialize_tuple(len, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_tuple_struct<V>(
@@ -252,17 +276,15 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_tuple_struct(name, len, Visitor::new(visitor, param))
+            .deserialize_tuple_struct(name, len, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_map(Visitor::new(visitor, param))
+        self.de.deserialize_map(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_struct<V>(
@@ -274,9 +296,8 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_struct(name, fields, Visitor::new(visitor, param))
+            .deserialize_struct(name, fields, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_enum<V>(
@@ -288,26 +309,24 @@ where
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_enum(name, variants, Visitor::new(visitor, param))
+            .deserialize_enum(name, variants, Visitor::new(visitor, self.param))
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.de
-            .deserialize_ignored_any(Visitor::new(visitor, param))
+            .deserialize_ignored_any(Visitor::new(visitor, self.param))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.de.deserialize_identifier(Visitor::new(visitor, param))
+        self.de
+            .deserialize_identifier(Visitor::new(visitor, self.param))
     }
 
     fn is_human_readable(&self) -> bool {
@@ -340,208 +359,263 @@ where
     where
         E: de::Error,
     {
-        self.delegate.visit_bool(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_bool(v))
     }
 
     fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_i8(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_i8(v))
     }
 
     fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_i16(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_i16(v))
     }
 
     fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_i32(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_i32(v))
     }
 
     fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_i64(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_i64(v))
     }
 
     fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_i128(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_i128(v))
     }
 
     fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_u8(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_u8(v))
     }
 
     fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_u16(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_u16(v))
     }
 
     fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_u32(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_u32(v))
     }
 
     fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_u64(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_u64(v))
     }
 
     fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_u128(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_u128(v))
     }
 
     fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_f32(v)
+        annotate_visit(&self.param, self.delegate.visit_f32(v))
     }
 
     fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_f64(v)
+        annotate_visit(&self.param, self.delegate.visit_f64(v))
     }
 
     fn visit_char<E>(self, v: char) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_char(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_char(v))
     }
 
     fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_str(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_str(v))
     }
 
     fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_borrowed_str(v)
+        self.param.record_key(v);
+        annotate_visit(&self.param, self.delegate.visit_borrowed_str(v))
     }
 
     fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_string(v)
+        self.param.record_key(&v);
+        annotate_visit(&self.param, self.delegate.visit_string(v))
     }
 
     fn visit_unit<E>(self) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_unit()
+        annotate_visit(&self.param, self.delegate.visit_unit())
     }
 
     fn visit_none<E>(self) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_none()
+        annotate_visit(&self.param, self.delegate.visit_none())
     }
 
     fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            self.delegate.visit_some(Deserializer {
-                de: deserializer,
-                red_zone: self.param.red_zone,
-                stack_size: self.param.stack_size,
+        let result = self
+            .param
+            .grow(|param| {
+                self.delegate.visit_some(Deserializer {
+                    de: deserializer,
+                    param,
+                })
             })
-        })
+            .unwrap_or_else(|error| Err(de::Error::custom(error)));
+        annotate_visit(&self.param, result)
     }
 
     fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            self.delegate.visit_newtype_struct(Deserializer {
-                de: deserializer,
-                red_zone: self.param.red_zone,
-                stack_size: self.param.stack_size,
+        let result = self
+            .param
+            .grow(|param| {
+                self.delegate.visit_newtype_struct(Deserializer {
+                    de: deserializer,
+                    param,
+                })
             })
-        })
+            .unwrap_or_else(|error| Err(de::Error::custom(error)));
+        annotate_visit(&self.param, result)
     }
 
     fn visit_seq<A>(self, visitor: A) -> Result<Self::Value, A::Error>
     where
         A: de::SeqAccess<'de>,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            self.delegate.visit_seq(SeqAccess::new(visitor, self.param))
-        })
+        let result = self
+            .param
+            .clone()
+            .nest()
+            .and_then(|param| {
+                param.grow(|param| self.delegate.visit_seq(SeqAccess::new(visitor, param)))
+            })
+            .unwrap_or_else(|error| Err(de::Error::custom(error)));
+        annotate_visit(&self.param, result)
     }
 
     fn visit_map<A>(self, visitor: A) -> Result<Self::Value, A::Error>
     where
         A: de::MapAccess<'de>,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            self.delegate.visit_map(MapAccess::new(visitor, self.param))
-        })
+        let result = self
+            .param
+            .clone()
+            .nest()
+            .and_then(|param| {
+                param.grow(|param| self.delegate.visit_map(MapAccess::new(visitor, param)))
+            })
+            .unwrap_or_else(|error| Err(de::Error::custom(error)));
+        annotate_visit(&self.param, result)
     }
 
     fn visit_enum<A>(self, visitor: A) -> Result<Self::Value, A::Error>
     where
         A: de::EnumAccess<'de>,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            self.delegate
-                .visit_enum(EnumAccess::new(visitor, self.param))
-        })
+        let result = self
+            .param
+            .clone()
+            .nest()
+            .and_then(|param| {
+                param.grow(|param| self.delegate.visit_enum(EnumAccess::new(visitor, param)))
+            })
+            .unwrap_or_else(|error| Err(de::Error::custom(error)));
+        annotate_visit(&self.param, result)
     }
 
     fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_bytes(v)
+        annotate_visit(&self.param, self.delegate.visit_bytes(v))
     }
 
     fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_borrowed_bytes(v)
+        annotate_visit(&self.param, self.delegate.visit_borrowed_bytes(v))
     }
 
     fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
     where
         E: de::Error,
     {
-        self.delegate.visit_byte_buf(v)
+        annotate_visit(&self.param, self.delegate.visit_byte_buf(v))
+    }
+}
+
+// Errors from a visitor are annotated as the Visitor adapter returns them,
+// before they reach the wrapped deserializer, which may go on to add a
+// position of its own to the message. Annotating only after that, in SeqAccess
+// and the like, would leave the position in the middle of the message, and
+// give formats which add a position to custom errors the chance to add it
+// twice. Outside of any sequence, map, or enum there is no location to report.
+fn annotate_visit<T, E>(param: &Param, result: Result<T, E>) -> Result<T, E>
+where
+    E: de::Error,
+{
+    if param.depth == 0 {
+        result
+    } else {
+        param.annotate(result)
     }
 }
 
@@ -568,9 +642,18 @@ where
         V: de::DeserializeSeed<'de>,
     {
         let param = self.param;
-        self.delegate
-            .variant_seed(DeserializeSeed::new(seed, param))
-            .map(|(v, vis)| (v, VariantAccess::new(vis, param)))
+        param.start_access();
+        param.push_path(Segment::Name(None));
+        let result = self
+            .delegate
+            .variant_seed(DeserializeSeed::new(seed, param.key()));
+        match param.annotate(result) {
+            Ok((v, vis)) => Ok((v, VariantAccess::new(vis, param))),
+            Err(err) => {
+                param.pop_path();
+                Err(err)
+            }
+        }
     }
 }
 
@@ -592,23 +675,37 @@ where
     type Error = D::Error;
 
     fn unit_variant(self) -> Result<(), D::Error> {
-        self.delegate.unit_variant()
+        self.param.start_access();
+        let result = self.delegate.unit_variant();
+        let result = self.param.annotate(result);
+        self.param.pop_path();
+        result
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, D::Error>
     where
         T: de::DeserializeSeed<'de>,
     {
-        self.delegate
-            .newtype_variant_seed(DeserializeSeed::new(seed, self.param))
+        self.param.start_access();
+        let result = self
+            .delegate
+            .newtype_variant_seed(DeserializeSeed::new(seed, self.param.clone()));
+        let result = self.param.annotate(result);
+        self.param.pop_path();
+        result
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, D::Error>
     where
         V: de::Visitor<'de>,
     {
-        self.delegate
-            .tuple_variant(len, Visitor::new(visitor, self.param))
+        self.param.start_access();
+        let result = self
+            .delegate
+            .tuple_variant(len, Visitor::new(visitor, self.param.clone()));
+        let result = self.param.annotate(result);
+        self.param.pop_path();
+        result
     }
 
     fn struct_variant<V>(
@@ -619,18 +716,23 @@ where
     where
         V: de::Visitor<'de>,
     {
-        self.delegate
-            .struct_variant(fields, Visitor::new(visitor, self.param))
+        self.param.start_access();
+        let result = self
+            .delegate
+            .struct_variant(fields, Visitor::new(visitor, self.param.clone()));
+        let result = self.param.annotate(result);
+        self.param.pop_path();
+        result
     }
 }
 
-struct DeserializeSeed<S> {
+pub struct DeserializeSeed<S> {
     delegate: S,
     param: Param,
 }
 
 impl<S> DeserializeSeed<S> {
-    fn new(delegate: S, param: Param) -> Self {
+    pub fn new(delegate: S, param: Param) -> Self {
         DeserializeSeed { delegate, param }
     }
 }
@@ -647,8 +749,7 @@ where
     {
         self.delegate.deserialize(Deserializer {
             de: deserializer,
-            red_zone: self.param.red_zone,
-            stack_size: self.param.stack_size,
+            param: self.param,
         })
     }
 }
@@ -656,11 +757,16 @@ where
 struct SeqAccess<D> {
     delegate: D,
     param: Param,
+    index: usize,
 }
 
 impl<D> SeqAccess<D> {
     fn new(delegate: D, param: Param) -> Self {
-        SeqAccess { delegate, param }
+        SeqAccess {
+            delegate,
+            param,
+            index: 0,
+        }
     }
 }
 
@@ -674,8 +780,15 @@ where
     where
         T: de::DeserializeSeed<'de>,
     {
-        self.delegate
-            .next_element_seed(DeserializeSeed::new(seed, self.param))
+        self.param.start_access();
+        self.param.push_path(Segment::Index(self.index));
+        let result = self
+            .delegate
+            .next_element_seed(DeserializeSeed::new(seed, self.param.clone()));
+        let result = self.param.annotate(result);
+        self.param.pop_path();
+        self.index += 1;
+        result
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -686,11 +799,33 @@ where
 struct MapAccess<D> {
     delegate: D,
     param: Param,
+    // Whether the path ends with the key of an entry whose value is next.
+    has_key: bool,
 }
 
 impl<D> MapAccess<D> {
     fn new(delegate: D, param: Param) -> Self {
-        MapAccess { delegate, param }
+        MapAccess {
+            delegate,
+            param,
+            has_key: false,
+        }
+    }
+
+    fn pop_key(&mut self) {
+        if self.has_key {
+            self.param.pop_path();
+            self.has_key = false;
+        }
+    }
+}
+
+// A visitor may stop after a key without asking for its value, as derived
+// Deserialize impls do for a duplicate field. The key must not be left in the
+// path for whoever handles the error.
+impl<D> Drop for MapAccess<D> {
+    fn drop(&mut self) {
+        self.pop_key();
     }
 }
 
@@ -704,16 +839,31 @@ where
     where
         K: de::DeserializeSeed<'de>,
     {
-        self.delegate
-            .next_key_seed(DeserializeSeed::new(seed, self.param))
+        self.param.start_access();
+        self.pop_key();
+        self.param.push_path(Segment::Name(None));
+        let result = self
+            .delegate
+            .next_key_seed(DeserializeSeed::new(seed, self.param.key()));
+        let result = self.param.annotate(result);
+        match result {
+            Ok(Some(_)) => self.has_key = true,
+            Ok(None) | Err(_) => self.param.pop_path(),
+        }
+        result
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, D::Error>
     where
         V: de::DeserializeSeed<'de>,
     {
-        self.delegate
-            .next_value_seed(DeserializeSeed::new(seed, self.param))
+        self.param.start_access();
+        let result = self
+            .delegate
+            .next_value_seed(DeserializeSeed::new(seed, self.param.clone()));
+        let result = self.param.annotate(result);
+        self.pop_key();
+        result
     }
 
     fn size_hint(&self) -> Option<usize> {
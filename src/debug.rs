@@ -0,0 +1,557 @@
+use alloc::string::{String, ToString as _};
+use core::fmt::{self, Debug, Display, Write as _};
+use serde::ser::{self, Serialize};
+
+/// Format any serializable value the way a derived `Debug` impl would, growing
+/// the stack as needed.
+///
+/// The value is serialized through this crate's
+/// [`Serializer`][crate::Serializer] into a serializer which writes to the
+/// `fmt::Formatter`. Structs, enums, sequences, and maps are rendered the same
+/// as the formatter's `debug_struct`, `debug_tuple`, `debug_list` and
+/// `debug_map` builders would, in both the `{:?}` and the multi-line `{:#?}`
+/// form. Fields skipped by the `Serialize` impl are not shown, and any
+/// renaming applies.
+///
+/// If the value fails to serialize, for example by exceeding the default
+/// configuration's `max_depth`, which without the `std` feature is 128, the
+/// error message is written as `<error: ...>` in place of the part of the
+/// value that failed, rather than failing the whole formatting operation.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut value = Value::Null;
+/// for _ in 0..100000 {
+///     value = Value::Array(vec![value]);
+/// }
+///
+/// let debug = format!("{:?}", serde_stacker::debug(&value));
+/// assert_eq!(debug.len(), 100000 + "()".len() + 100000);
+/// # let mut stack = vec![value];
+/// # while let Some(value) = stack.pop() {
+/// #     if let Value::Array(array) = value {
+/// #         stack.extend(array);
+/// #     }
+/// # }
+/// ```
+pub fn debug<'a, T>(value: &'a T) -> impl Debug + 'a
+where
+    T: ?Sized + Serialize,
+{
+    Stacked(value)
+}
+
+struct Stacked<'a, T: ?Sized>(&'a T);
+
+impl<'a, T> Debug for Stacked<'a, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let serializer = crate::Serializer::new(Formatter {
+            formatter: &mut *formatter,
+            indent: 0,
+        });
+        match self.0.serialize(serializer) {
+            Ok(()) => Ok(()),
+            Err(error) => error.write_to(formatter),
+        }
+    }
+}
+
+// A value which is already inside of the adapter, to be formatted as part of
+// an enclosing one at the given level of indentation.
+fn nested<T>(formatter: &mut fmt::Formatter, indent: usize, value: &T) -> Result<(), Error>
+where
+    T: ?Sized + Serialize,
+{
+    match value.serialize(Formatter {
+        formatter: &mut *formatter,
+        indent,
+    }) {
+        Ok(()) => Ok(()),
+        Err(error) => Ok(error.write_to(formatter)?),
+    }
+}
+
+// Writes to the fmt::Formatter directly rather than through its debug_struct
+// and similar builders. In the alternate form, those wrap the formatter at
+// each level of nesting in an adapter which indents what is written through
+// it, so a write at depth N would pass through N adapters on the current
+// stack. Instead, this keeps count of the depth and writes the indentation
+// itself.
+struct Formatter<'a, 'b> {
+    formatter: &'a mut fmt::Formatter<'b>,
+    // Number of enclosing structs, enums, sequences, and maps.
+    indent: usize,
+}
+
+#[derive(Debug)]
+enum Error {
+    Fmt,
+    // The value's Serialize impl or the adapter failed, and the message is to
+    // be shown in place of the value.
+    Custom(String),
+}
+
+impl Error {
+    fn write_to(self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Fmt => Err(fmt::Error),
+            Error::Custom(msg) => write!(formatter, "<error: {msg}>"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Fmt => formatter.write_str("error formatting value"),
+            Error::Custom(msg) => formatter.write_str(msg),
+        }
+    }
+}
+
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(fmt::Error: fmt::Error) -> Self {
+        Error::Fmt
+    }
+}
+
+impl<'a, 'b> Formatter<'a, 'b> {
+    fn debug(self, value: impl Debug) -> Result<(), Error> {
+        Ok(value.fmt(self.formatter)?)
+    }
+
+    fn compound(self, name: &'static str, kind: Kind) -> Result<Compound<'a, 'b>, Error> {
+        self.formatter.write_str(name)?;
+        Ok(Compound {
+            formatter: self.formatter,
+            indent: self.indent,
+            kind,
+            empty_name: name.is_empty(),
+            len: 0,
+        })
+    }
+
+    fn newtype<T>(self, name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut tuple = self.compound(name, Kind::Tuple)?;
+        ser::SerializeTuple::serialize_element(&mut tuple, value)?;
+        ser::SerializeTuple::end(tuple)
+    }
+}
+
+impl<'a, 'b> ser::Serializer for Formatter<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, 'b>;
+    type SerializeTuple = Compound<'a, 'b>;
+    type SerializeTupleStruct = Compound<'a, 'b>;
+    type SerializeTupleVariant = Compound<'a, 'b>;
+    type SerializeMap = Compound<'a, 'b>;
+    type SerializeStruct = Compound<'a, 'b>;
+    type SerializeStructVariant = Compound<'a, 'b>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.debug(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        let mut list = self.compound("", Kind::List)?;
+        for byte in v {
+            ser::SerializeSeq::serialize_element(&mut list, byte)?;
+        }
+        ser::SerializeSeq::end(list)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(self.formatter.write_str("None")?)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.newtype("Some", value)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(self.formatter.write_str("()")?)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        Ok(self.formatter.write_str(name)?)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        Ok(self.formatter.write_str(variant)?)
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.newtype(name, value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.newtype(variant, value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, 'b>, Error> {
+        self.compound("", Kind::List)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a, 'b>, Error> {
+        self.compound("", Kind::Tuple)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, Error> {
+        self.compound(name, Kind::Tuple)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, Error> {
+        self.compound(variant, Kind::Tuple)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, 'b>, Error> {
+        self.compound("", Kind::Map)
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Compound<'a, 'b>, Error> {
+        self.compound(name, Kind::Struct)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, Error> {
+        self.compound(variant, Kind::Struct)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Kind {
+    // `Name(a, b)`, or `Name` if there are no fields.
+    Tuple,
+    // `Name { a: 1, b: 2 }`, or `Name` if there are no fields.
+    Struct,
+    // `[a, b]`
+    List,
+    // `{k: v}`
+    Map,
+}
+
+struct Compound<'a, 'b> {
+    formatter: &'a mut fmt::Formatter<'b>,
+    indent: usize,
+    kind: Kind,
+    empty_name: bool,
+    // Number of fields, elements, or entries begun so far.
+    len: usize,
+}
+
+impl<'a, 'b> Compound<'a, 'b> {
+    // Writes what goes before the next field, element, or map key.
+    fn begin_entry(&mut self) -> Result<(), Error> {
+        if self.len == 0 {
+            self.formatter.write_str(match self.kind {
+                Kind::Tuple => "(",
+                Kind::Struct => " {",
+                Kind::List => "[",
+                Kind::Map => "{",
+            })?;
+        }
+        if self.formatter.alternate() {
+            self.formatter.write_char('\n')?;
+            indent(self.formatter, self.indent + 1)?;
+        } else if self.len > 0 {
+            self.formatter.write_str(", ")?;
+        } else if self.kind == Kind::Struct {
+            self.formatter.write_char(' ')?;
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        nested(self.formatter, self.indent + 1, value)?;
+        if self.formatter.alternate() {
+            self.formatter.write_char(',')?;
+        }
+        Ok(())
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.begin_entry()?;
+        self.value(value)
+    }
+
+    fn field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.begin_entry()?;
+        self.formatter.write_str(key)?;
+        self.formatter.write_str(": ")?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        if self.len == 0 {
+            self.formatter.write_str(match self.kind {
+                Kind::Tuple | Kind::Struct => "",
+                Kind::List => "[]",
+                Kind::Map => "{}",
+            })?;
+            return Ok(());
+        }
+        if self.formatter.alternate() {
+            self.formatter.write_char('\n')?;
+            indent(self.formatter, self.indent)?;
+        } else if self.kind == Kind::Struct {
+            self.formatter.write_char(' ')?;
+        } else if self.kind == Kind::Tuple && self.empty_name && self.len == 1 {
+            self.formatter.write_char(',')?;
+        }
+        self.formatter.write_str(match self.kind {
+            Kind::Tuple => ")",
+            Kind::Struct | Kind::Map => "}",
+            Kind::List => "]",
+        })?;
+        Ok(())
+    }
+}
+
+fn indent(formatter: &mut fmt::Formatter, level: usize) -> fmt::Result {
+    const SPACES: &str = "                                                                ";
+    let mut remaining = level * 4;
+    while remaining > 0 {
+        let n = remaining.min(SPACES.len());
+        formatter.write_str(&SPACES[..n])?;
+        remaining -= n;
+    }
+    Ok(())
+}
+
+impl<'a, 'b> ser::SerializeSeq for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeTuple for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeTupleStruct for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeTupleVariant for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeMap for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.begin_entry()?;
+        nested(self.formatter, self.indent + 1, key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.formatter.write_str(": ")?;
+        self.value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeStruct for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'a, 'b> ser::SerializeStructVariant for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
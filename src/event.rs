@@ -0,0 +1,1047 @@
+// Serializer which flattens a value into a sequence of events, one per call
+// to a serializer method, for comparing and hashing values of any type
+// through their Serialize impls, and a Deserializer which replays them.
+//
+// Compound values are delimited by an End event rather than by their length,
+// which some Serialize impls do not know up front.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use core::hash::{Hash, Hasher};
+use core::slice;
+use serde::de::{self, value, DeserializeOwned, IntoDeserializer as _};
+use serde::ser::{self, Serialize};
+
+/// Compare two values for equality by their `Serialize` impls, growing the
+/// stack as needed.
+///
+/// The values are equal if serializing them makes the same sequence of calls
+/// with the same arguments into a `Serializer`, meaning the same variants of
+/// the same enums, fields of the same structs, elements of sequences and
+/// entries of maps in the same order, and equal primitive values. Floating
+/// point numbers compare as in `PartialEq`, so NaN is not equal to itself.
+///
+/// Comparing `a` and `b` involves recording the serialization of `a` on the
+/// heap, so takes memory proportional to the size of `a`.
+///
+/// # Errors
+///
+/// Returns an error if `a` fails to serialize, or `b` fails to serialize
+/// before it is found to differ from `a`, including by exceeding the default
+/// configuration's `max_depth`, which without the `std` feature is 128. In
+/// that case no answer is given, rather than one based on only part of the
+/// values.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut a = Value::Null;
+/// let mut b = Value::Null;
+/// for _ in 0..100000 {
+///     a = Value::Array(vec![a]);
+///     b = Value::Array(vec![b]);
+/// }
+///
+/// assert!(serde_stacker::eq(&a, &b).unwrap());
+/// # for value in [a, b] {
+/// #     let mut stack = vec![value];
+/// #     while let Some(value) = stack.pop() {
+/// #         if let Value::Array(array) = value {
+/// #             stack.extend(array);
+/// #         }
+/// #     }
+/// # }
+/// ```
+pub fn eq<A, B>(a: &A, b: &B) -> Result<bool, value::Error>
+where
+    A: ?Sized + Serialize,
+    B: ?Sized + Serialize,
+{
+    let mut recorded = Vec::new();
+    serialize(a, &mut Record(&mut recorded))?;
+    let mut compare = Compare(recorded.iter());
+    match serialize(b, &mut compare) {
+        Ok(()) => Ok(compare.0.next().is_none()),
+        Err(CompareError::Unequal) => Ok(false),
+        Err(CompareError::Failed(error)) => Err(error),
+    }
+}
+
+/// Feed a value into a `Hasher` by its `Serialize` impl, growing the stack as
+/// needed.
+///
+/// Values which are [`eq`] hash the same. In particular `0.0` and `-0.0`,
+/// which are equal, hash the same.
+///
+/// # Errors
+///
+/// Returns an error if the value fails to serialize, including by exceeding
+/// the default configuration's `max_depth`, which without the `std` feature
+/// is 128. The part of the value before the failure has been fed into the
+/// hasher by then, so its state should be discarded.
+///
+/// ```
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::Hasher;
+///
+/// let mut hasher = DefaultHasher::new();
+/// serde_stacker::hash(&vec![1, 2, 3], &mut hasher).unwrap();
+/// let hash = hasher.finish();
+/// # let _ = hash;
+/// ```
+pub fn hash<T, H>(value: &T, hasher: &mut H) -> Result<(), value::Error>
+where
+    T: ?Sized + Serialize,
+    H: ?Sized + Hasher,
+{
+    serialize(value, &mut HashEvents(hasher))
+}
+
+/// Clone a value by serializing it and deserializing a new value from the
+/// result, growing the stack as needed on both sides.
+///
+/// This is a replacement for derived `Clone` impls, which recurse on the
+/// current stack. The value is serialized through this crate's
+/// [`Serializer`][crate::Serializer] into an in-memory sequence of serializer
+/// calls, not into any data format, from which the clone is deserialized
+/// through this crate's [`Deserializer`][crate::Deserializer]. The recorded
+/// sequence takes memory proportional to the size of the value.
+///
+/// Fields skipped by the `Serialize` impl get whatever value the
+/// `Deserialize` impl gives them, and an error is returned if the two impls
+/// do not agree on the shape of the data.
+///
+/// # Errors
+///
+/// Returns an error if the value fails to serialize, if the clone fails to
+/// deserialize from what was serialized, or if the default configuration's
+/// `max_depth` is exceeded on either side, which without the `std` feature is
+/// 128.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut value = Value::Null;
+/// for _ in 0..100000 {
+///     value = Value::Array(vec![value]);
+/// }
+///
+/// let clone: Value = serde_stacker::deep_clone(&value).unwrap();
+/// assert!(serde_stacker::eq(&value, &clone).unwrap());
+/// # for value in [value, clone] {
+/// #     let mut stack = vec![value];
+/// #     while let Some(value) = stack.pop() {
+/// #         if let Value::Array(array) = value {
+/// #             stack.extend(array);
+/// #         }
+/// #     }
+/// # }
+/// ```
+pub fn deep_clone<T>(value: &T) -> Result<T, value::Error>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut recorded = Vec::new();
+    serialize(value, &mut Record(&mut recorded))?;
+    let mut replay = Replay(recorded.iter());
+    let clone = T::deserialize(crate::Deserializer::new(&mut replay))?;
+    if replay.0.next().is_some() {
+        return Err(de::Error::custom("trailing data after value"));
+    }
+    Ok(clone)
+}
+
+fn serialize<T, S>(value: &T, sink: &mut S) -> Result<(), S::Error>
+where
+    T: ?Sized + Serialize,
+    S: ?Sized + Sink,
+{
+    value.serialize(crate::Serializer::new(Events(sink)))
+}
+
+#[derive(PartialEq)]
+enum Event<'a> {
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Str(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    None,
+    Some,
+    Unit,
+    UnitStruct(&'static str),
+    UnitVariant(&'static str, u32, &'static str),
+    NewtypeStruct(&'static str),
+    NewtypeVariant(&'static str, u32, &'static str),
+    Seq,
+    Tuple,
+    TupleStruct(&'static str),
+    TupleVariant(&'static str, u32, &'static str),
+    Map,
+    Struct(&'static str),
+    StructVariant(&'static str, u32, &'static str),
+    Field(&'static str),
+    SkipField(&'static str),
+    End,
+}
+
+impl<'a> Event<'a> {
+    fn into_owned(self) -> Event<'static> {
+        match self {
+            Event::Bool(v) => Event::Bool(v),
+            Event::I8(v) => Event::I8(v),
+            Event::I16(v) => Event::I16(v),
+            Event::I32(v) => Event::I32(v),
+            Event::I64(v) => Event::I64(v),
+            Event::I128(v) => Event::I128(v),
+            Event::U8(v) => Event::U8(v),
+            Event::U16(v) => Event::U16(v),
+            Event::U32(v) => Event::U32(v),
+            Event::U64(v) => Event::U64(v),
+            Event::U128(v) => Event::U128(v),
+            Event::F32(v) => Event::F32(v),
+            Event::F64(v) => Event::F64(v),
+            Event::Char(v) => Event::Char(v),
+            Event::Str(v) => Event::Str(Cow::Owned(v.into_owned())),
+            Event::Bytes(v) => Event::Bytes(Cow::Owned(v.into_owned())),
+            Event::None => Event::None,
+            Event::Some => Event::Some,
+            Event::Unit => Event::Unit,
+            Event::UnitStruct(name) => Event::UnitStruct(name),
+            Event::UnitVariant(name, index, variant) => Event::UnitVariant(name, index, variant),
+            Event::NewtypeStruct(name) => Event::NewtypeStruct(name),
+            Event::NewtypeVariant(name, index, variant) => {
+                Event::NewtypeVariant(name, index, variant)
+            }
+            Event::Seq => Event::Seq,
+            Event::Tuple => Event::Tuple,
+            Event::TupleStruct(name) => Event::TupleStruct(name),
+            Event::TupleVariant(name, index, variant) => Event::TupleVariant(name, index, variant),
+            Event::Map => Event::Map,
+            Event::Struct(name) => Event::Struct(name),
+            Event::StructVariant(name, index, variant) => {
+                Event::StructVariant(name, index, variant)
+            }
+            Event::Field(key) => Event::Field(key),
+            Event::SkipField(key) => Event::SkipField(key),
+            Event::End => Event::End,
+        }
+    }
+}
+
+impl<'a> Hash for Event<'a> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        core::mem::discriminant(self).hash(hasher);
+        match self {
+            Event::Bool(v) => v.hash(hasher),
+            Event::I8(v) => v.hash(hasher),
+            Event::I16(v) => v.hash(hasher),
+            Event::I32(v) => v.hash(hasher),
+            Event::I64(v) => v.hash(hasher),
+            Event::I128(v) => v.hash(hasher),
+            Event::U8(v) => v.hash(hasher),
+            Event::U16(v) => v.hash(hasher),
+            Event::U32(v) => v.hash(hasher),
+            Event::U64(v) => v.hash(hasher),
+            Event::U128(v) => v.hash(hasher),
+            // Equal numbers must hash the same, so -0.0 is hashed as 0.0.
+            Event::F32(v) => (v + 0.0).to_bits().hash(hasher),
+            Event::F64(v) => (v + 0.0).to_bits().hash(hasher),
+            Event::Char(v) => v.hash(hasher),
+            Event::Str(v) => v.hash(hasher),
+            Event::Bytes(v) => v.hash(hasher),
+            Event::UnitStruct(name)
+            | Event::NewtypeStruct(name)
+            | Event::TupleStruct(name)
+            | Event::Struct(name) => name.hash(hasher),
+            Event::UnitVariant(name, index, variant)
+            | Event::NewtypeVariant(name, index, variant)
+            | Event::TupleVariant(name, index, variant)
+            | Event::StructVariant(name, index, variant) => {
+                name.hash(hasher);
+                index.hash(hasher);
+                variant.hash(hasher);
+            }
+            Event::Field(key) | Event::SkipField(key) => key.hash(hasher),
+            Event::None
+            | Event::Some
+            | Event::Unit
+            | Event::Seq
+            | Event::Tuple
+            | Event::Map
+            | Event::End => {}
+        }
+    }
+}
+
+trait Sink {
+    type Error: ser::Error;
+
+    fn event(&mut self, event: Event) -> Result<(), Self::Error>;
+}
+
+struct Record<'a>(&'a mut Vec<Event<'static>>);
+
+impl<'a> Sink for Record<'a> {
+    type Error = value::Error;
+
+    fn event(&mut self, event: Event) -> Result<(), value::Error> {
+        self.0.push(event.into_owned());
+        Ok(())
+    }
+}
+
+struct Compare<'a>(slice::Iter<'a, Event<'static>>);
+
+impl<'a> Sink for Compare<'a> {
+    type Error = CompareError;
+
+    fn event(&mut self, event: Event) -> Result<(), CompareError> {
+        match self.0.next() {
+            Some(expected) if *expected == event => Ok(()),
+            _ => Err(CompareError::Unequal),
+        }
+    }
+}
+
+struct HashEvents<'a, H: ?Sized>(&'a mut H);
+
+impl<'a, H> Sink for HashEvents<'a, H>
+where
+    H: ?Sized + Hasher,
+{
+    type Error = value::Error;
+
+    fn event(&mut self, event: Event) -> Result<(), value::Error> {
+        event.hash(&mut self.0);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+enum CompareError {
+    // The value does not match the recorded one, so the rest of it is not of
+    // interest.
+    Unequal,
+    // The value failed to serialize.
+    Failed(value::Error),
+}
+
+impl Display for CompareError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompareError::Unequal => formatter.write_str("values are not equal"),
+            CompareError::Failed(error) => Display::fmt(error, formatter),
+        }
+    }
+}
+
+impl ser::StdError for CompareError {}
+
+impl ser::Error for CompareError {
+    fn custom<T: Display>(msg: T) -> Self {
+        CompareError::Failed(ser::Error::custom(msg))
+    }
+}
+
+struct Events<'a, S: ?Sized>(&'a mut S);
+
+impl<'a, S> Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    fn nested<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(Events(&mut *self.0))
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        self.0.event(Event::End)
+    }
+}
+
+impl<'a, S> ser::Serializer for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), S::Error> {
+        self.0.event(Event::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), S::Error> {
+        self.0.event(Event::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), S::Error> {
+        self.0.event(Event::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), S::Error> {
+        self.0.event(Event::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), S::Error> {
+        self.0.event(Event::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), S::Error> {
+        self.0.event(Event::I128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), S::Error> {
+        self.0.event(Event::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), S::Error> {
+        self.0.event(Event::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), S::Error> {
+        self.0.event(Event::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), S::Error> {
+        self.0.event(Event::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), S::Error> {
+        self.0.event(Event::U128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), S::Error> {
+        self.0.event(Event::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), S::Error> {
+        self.0.event(Event::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), S::Error> {
+        self.0.event(Event::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), S::Error> {
+        self.0.event(Event::Str(Cow::Borrowed(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), S::Error> {
+        self.0.event(Event::Bytes(Cow::Borrowed(v)))
+    }
+
+    fn serialize_none(self) -> Result<(), S::Error> {
+        self.0.event(Event::None)
+    }
+
+    fn serialize_some<T>(mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.event(Event::Some)?;
+        self.nested(value)
+    }
+
+    fn serialize_unit(self) -> Result<(), S::Error> {
+        self.0.event(Event::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), S::Error> {
+        self.0.event(Event::UnitStruct(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), S::Error> {
+        self.0
+            .event(Event::UnitVariant(name, variant_index, variant))
+    }
+
+    fn serialize_newtype_struct<T>(mut self, name: &'static str, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.event(Event::NewtypeStruct(name))?;
+        self.nested(value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        mut self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0
+            .event(Event::NewtypeVariant(name, variant_index, variant))?;
+        self.nested(value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, S::Error> {
+        self.0.event(Event::Seq)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, S::Error> {
+        self.0.event(Event::Tuple)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Self, S::Error> {
+        self.0.event(Event::TupleStruct(name))?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, S::Error> {
+        self.0
+            .event(Event::TupleVariant(name, variant_index, variant))?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, S::Error> {
+        self.0.event(Event::Map)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self, S::Error> {
+        self.0.event(Event::Struct(name))?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, S::Error> {
+        self.0
+            .event(Event::StructVariant(name, variant_index, variant))?;
+        Ok(self)
+    }
+}
+
+impl<'a, S> ser::SerializeSeq for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeTuple for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeTupleStruct for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeTupleVariant for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeMap for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.nested(value)
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeStruct for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.event(Event::Field(key))?;
+        self.nested(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), S::Error> {
+        self.0.event(Event::SkipField(key))
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+impl<'a, S> ser::SerializeStructVariant for Events<'a, S>
+where
+    S: ?Sized + Sink,
+{
+    type Ok = ();
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.event(Event::Field(key))?;
+        self.nested(value)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> Result<(), S::Error> {
+        self.0.event(Event::SkipField(key))
+    }
+
+    fn end(self) -> Result<(), S::Error> {
+        Events::end(self)
+    }
+}
+
+struct Replay<'a>(slice::Iter<'a, Event<'static>>);
+
+impl<'a> Replay<'a> {
+    fn next(&mut self) -> Result<&'a Event<'static>, value::Error> {
+        self.0
+            .next()
+            .ok_or_else(|| de::Error::custom("unexpected end of data"))
+    }
+
+    fn peek(&self) -> Option<&'a Event<'static>> {
+        self.0.as_slice().first()
+    }
+
+    // Visits the elements or entries following a Seq or Map event, up to and
+    // including the End event.
+    fn visit_compound<'de, V>(&mut self, visitor: V, map: bool) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let mut access = Compound {
+            replay: self,
+            done: false,
+        };
+        let value = if map {
+            visitor.visit_map(&mut access)?
+        } else {
+            visitor.visit_seq(&mut access)?
+        };
+        if !access.done && !matches!(access.replay.next()?, Event::End) {
+            return Err(de::Error::custom("trailing data in sequence or map"));
+        }
+        Ok(value)
+    }
+}
+
+impl<'de, 'r, 'a> de::Deserializer<'de> for &'r mut Replay<'a> {
+    type Error = value::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        // Newtype structs are transparent, as in most data formats.
+        let mut event = self.next()?;
+        while let Event::NewtypeStruct(_) = event {
+            event = self.next()?;
+        }
+        match event {
+            Event::Bool(v) => visitor.visit_bool(*v),
+            Event::I8(v) => visitor.visit_i8(*v),
+            Event::I16(v) => visitor.visit_i16(*v),
+            Event::I32(v) => visitor.visit_i32(*v),
+            Event::I64(v) => visitor.visit_i64(*v),
+            Event::I128(v) => visitor.visit_i128(*v),
+            Event::U8(v) => visitor.visit_u8(*v),
+            Event::U16(v) => visitor.visit_u16(*v),
+            Event::U32(v) => visitor.visit_u32(*v),
+            Event::U64(v) => visitor.visit_u64(*v),
+            Event::U128(v) => visitor.visit_u128(*v),
+            Event::F32(v) => visitor.visit_f32(*v),
+            Event::F64(v) => visitor.visit_f64(*v),
+            Event::Char(v) => visitor.visit_char(*v),
+            Event::Str(v) => visitor.visit_str(v),
+            Event::Bytes(v) => visitor.visit_bytes(v),
+            Event::None => visitor.visit_none(),
+            Event::Some => visitor.visit_some(self),
+            Event::Unit | Event::UnitStruct(_) => visitor.visit_unit(),
+            Event::UnitVariant(_, _, variant) => visitor.visit_str(variant),
+            // Other enum variants look like a map with a single entry, as in
+            // JSON, to a visitor which did not ask for an enum.
+            Event::NewtypeVariant(_, _, variant) => visitor.visit_map(VariantMap {
+                replay: self,
+                variant: Some(variant),
+                kind: Kind::Newtype,
+            }),
+            Event::TupleVariant(_, _, variant) => visitor.visit_map(VariantMap {
+                replay: self,
+                variant: Some(variant),
+                kind: Kind::Tuple,
+            }),
+            Event::StructVariant(_, _, variant) => visitor.visit_map(VariantMap {
+                replay: self,
+                variant: Some(variant),
+                kind: Kind::Struct,
+            }),
+            Event::Seq | Event::Tuple | Event::TupleStruct(_) => {
+                self.visit_compound(visitor, false)
+            }
+            Event::Map | Event::Struct(_) => self.visit_compound(visitor, true),
+            Event::NewtypeStruct(_) => unreachable!(),
+            Event::Field(_) | Event::SkipField(_) | Event::End => {
+                Err(de::Error::custom("expected a value"))
+            }
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        if let Some(Event::NewtypeStruct(_)) = self.peek() {
+            self.0.next();
+            visitor.visit_newtype_struct(self)
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        let (variant, kind) = match self.peek() {
+            Some(Event::UnitVariant(_, _, variant)) => (variant, Kind::Unit),
+            Some(Event::NewtypeVariant(_, _, variant)) => (variant, Kind::Newtype),
+            Some(Event::TupleVariant(_, _, variant)) => (variant, Kind::Tuple),
+            Some(Event::StructVariant(_, _, variant)) => (variant, Kind::Struct),
+            _ => return self.deserialize_any(visitor),
+        };
+        self.0.next();
+        visitor.visit_enum(Variant {
+            replay: self,
+            name: variant,
+            kind,
+        })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+struct Compound<'r, 'a> {
+    replay: &'r mut Replay<'a>,
+    done: bool,
+}
+
+impl<'r, 'a> Compound<'r, 'a> {
+    fn at_end(&mut self) -> bool {
+        if !self.done && matches!(self.replay.peek(), Some(Event::End)) {
+            self.replay.0.next();
+            self.done = true;
+        }
+        self.done
+    }
+}
+
+impl<'de, 'r, 'a> de::SeqAccess<'de> for Compound<'r, 'a> {
+    type Error = value::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, value::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.at_end() {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.replay).map(Some)
+    }
+}
+
+impl<'de, 'r, 'a> de::MapAccess<'de> for Compound<'r, 'a> {
+    type Error = value::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, value::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        while let Some(Event::SkipField(_)) = self.replay.peek() {
+            self.replay.0.next();
+        }
+        if self.at_end() {
+            return Ok(None);
+        }
+        if let Some(Event::Field(key)) = self.replay.peek() {
+            self.replay.0.next();
+            return seed.deserialize(key.into_deserializer()).map(Some);
+        }
+        seed.deserialize(&mut *self.replay).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, value::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.replay)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Kind {
+    Unit,
+    Newtype,
+    Tuple,
+    Struct,
+}
+
+struct Variant<'r, 'a> {
+    replay: &'r mut Replay<'a>,
+    name: &'static str,
+    kind: Kind,
+}
+
+impl<'de, 'r, 'a> de::EnumAccess<'de> for Variant<'r, 'a> {
+    type Error = value::Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), value::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.name.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'r, 'a> de::VariantAccess<'de> for Variant<'r, 'a> {
+    type Error = value::Error;
+
+    fn unit_variant(self) -> Result<(), value::Error> {
+        match self.kind {
+            Kind::Unit => Ok(()),
+            _ => Err(self.invalid_type(&"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, value::Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.kind {
+            Kind::Newtype => seed.deserialize(self.replay),
+            _ => Err(self.invalid_type(&"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.kind {
+            Kind::Tuple => self.replay.visit_compound(visitor, false),
+            _ => Err(self.invalid_type(&"tuple variant")),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.kind {
+            Kind::Struct => self.replay.visit_compound(visitor, true),
+            _ => Err(self.invalid_type(&"struct variant")),
+        }
+    }
+}
+
+impl<'r, 'a> Variant<'r, 'a> {
+    fn invalid_type(&self, expected: &dyn de::Expected) -> value::Error {
+        let unexpected = match self.kind {
+            Kind::Unit => de::Unexpected::UnitVariant,
+            Kind::Newtype => de::Unexpected::NewtypeVariant,
+            Kind::Tuple => de::Unexpected::TupleVariant,
+            Kind::Struct => de::Unexpected::StructVariant,
+        };
+        de::Error::invalid_type(unexpected, expected)
+    }
+}
+
+struct VariantMap<'r, 'a> {
+    replay: &'r mut Replay<'a>,
+    variant: Option<&'static str>,
+    kind: Kind,
+}
+
+impl<'de, 'r, 'a> de::MapAccess<'de> for VariantMap<'r, 'a> {
+    type Error = value::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, value::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.variant.take() {
+            Some(variant) => seed.deserialize(variant.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, value::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.kind {
+            Kind::Tuple | Kind::Struct => seed.deserialize(VariantContent {
+                replay: &mut *self.replay,
+                map: matches!(self.kind, Kind::Struct),
+            }),
+            Kind::Unit | Kind::Newtype => seed.deserialize(&mut *self.replay),
+        }
+    }
+}
+
+// The fields of a tuple or struct variant, which follow the variant's event
+// without a Seq or Map event of their own.
+struct VariantContent<'r, 'a> {
+    replay: &'r mut Replay<'a>,
+    map: bool,
+}
+
+impl<'de, 'r, 'a> de::Deserializer<'de> for VariantContent<'r, 'a> {
+    type Error = value::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, value::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.replay.visit_compound(visitor, self.map)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
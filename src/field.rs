@@ -0,0 +1,67 @@
+//! Stack growth for individual fields, for use with `#[serde(with = ...)]`.
+//!
+//! Often a single field is responsible for a data structure's recursion, for
+//! example the children of a node in a syntax tree. Annotating that field
+//! makes its serialization and deserialization check whether the stack is
+//! within `red_zone` bytes of its end, and if so continue on a newly allocated
+//! stack, using the default [`Config`].
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Node {
+//!     name: String,
+//!     #[serde(with = "serde_stacker::field")]
+//!     children: Vec<Node>,
+//! }
+//! ```
+//!
+//! Unlike wrapping a whole serializer or deserializer in the adapters from
+//! this crate, the check happens only at the annotated field, not at every
+//! level of nesting within it. This is what makes it usable on a field whose
+//! type contains the annotated struct again. Every cycle of the recursion
+//! needs to pass through an annotated field.
+//!
+//! Stack growth is the only protection these functions provide. They always
+//! use the `red_zone` and `stack_size` of the default [`Config`]; nothing
+//! counts nesting depth, so there is no `max_depth`, error annotation, or path
+//! tracking, and no `Stats` or `GrowthObserver` to attach. The `stack_budget`
+//! of an enclosing adapter, if any, does still apply. Without the `std`
+//! feature the stack cannot grow, and the field is serialized or deserialized
+//! as if it were not annotated.
+
+use crate::config::Config;
+use crate::param::Param;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{self, Serialize, Serializer};
+
+/// Serialize a field's value, growing the stack first if necessary.
+///
+/// # Errors
+///
+/// Returns the error from the value's `Serialize` impl, if any.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize,
+    S: Serializer,
+{
+    Param::new(Config::default())
+        .grow(|_param| value.serialize(serializer))
+        .map_err(ser::Error::custom)?
+}
+
+/// Deserialize a field's value, growing the stack first if necessary.
+///
+/// # Errors
+///
+/// Returns the error from the field type's `Deserialize` impl, if any.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Param::new(Config::default())
+        .grow(|_param| T::deserialize(deserializer))
+        .map_err(de::Error::custom)?
+}
@@ -0,0 +1,479 @@
+//! Helpers for working with deeply nested JSON through `serde_json`.
+//!
+//! The `from_*` functions set up a `serde_json::Deserializer` with its
+//! recursion limit disabled, wrap it in this crate's
+//! [`Deserializer`][crate::Deserializer], and check that the input contains no
+//! trailing data after the value. The `to_*` functions likewise serialize
+//! through this crate's [`Serializer`][crate::Serializer].
+//!
+//! The `Drop` impl of `serde_json::Value` is recursive, so a value that was
+//! deserialized successfully can still overflow the stack when it goes out of
+//! scope. See [`drop_value`] and [`SafeValue`].
+
+use crate::config::Config;
+use crate::param::Param;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde_json::de::{IoRead, Read, SliceRead, StrRead};
+use serde_json::Value;
+use std::io;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::string::String;
+use std::vec;
+use std::vec::Vec;
+
+fn from_trait<'de, R, T>(read: R, config: Config) -> serde_json::Result<T>
+where
+    R: Read<'de>,
+    T: Deserialize<'de>,
+{
+    let mut deserializer = serde_json::Deserializer::new(read);
+    deserializer.disable_recursion_limit();
+    let value = T::deserialize(crate::Deserializer::with_config(&mut deserializer, config))?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Deserialize an instance of type `T` from a string of JSON text, growing
+/// the stack as needed.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut json = String::new();
+/// for _ in 0..10000 {
+///     json = format!("[{}]", json);
+/// }
+///
+/// let value: Value = serde_stacker::json::from_str(&json).unwrap();
+/// serde_stacker::json::drop_value(value);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid JSON, does not match what `T`
+/// expects, or has trailing data other than whitespace.
+pub fn from_str<'a, T>(s: &'a str) -> serde_json::Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_str_with_config(s, Config::default())
+}
+
+/// Deserialize an instance of type `T` from a string of JSON text, using the
+/// given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid JSON, does not match what `T`
+/// expects, or has trailing data other than whitespace. Also returns an error
+/// once the input nests deeper than the configuration's `max_depth`, or needs
+/// more stack than its `stack_budget` allows.
+pub fn from_str_with_config<'a, T>(s: &'a str, config: Config) -> serde_json::Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_trait(StrRead::new(s), config)
+}
+
+/// Deserialize an instance of type `T` from bytes of JSON text, growing the
+/// stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid JSON, does not match what `T`
+/// expects, or has trailing data other than whitespace.
+pub fn from_slice<'a, T>(v: &'a [u8]) -> serde_json::Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_slice_with_config(v, Config::default())
+}
+
+/// Deserialize an instance of type `T` from bytes of JSON text, using the
+/// given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if the input is not valid JSON, does not match what `T`
+/// expects, or has trailing data other than whitespace. Also returns an error
+/// once the input nests deeper than the configuration's `max_depth`, or needs
+/// more stack than its `stack_budget` allows.
+pub fn from_slice_with_config<'a, T>(v: &'a [u8], config: Config) -> serde_json::Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_trait(SliceRead::new(v), config)
+}
+
+/// Deserialize an instance of type `T` from an I/O stream of JSON, growing
+/// the stack as needed.
+///
+/// As with `serde_json::from_reader`, the reader is not buffered internally,
+/// so wrapping it in a `std::io::BufReader` is advisable.
+///
+/// # Errors
+///
+/// Returns an error if reading from `rdr` fails, or if the input is not valid
+/// JSON, does not match what `T` expects, or has trailing data other than
+/// whitespace.
+pub fn from_reader<R, T>(rdr: R) -> serde_json::Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    from_reader_with_config(rdr, Config::default())
+}
+
+/// Deserialize an instance of type `T` from an I/O stream of JSON, using the
+/// given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if reading from `rdr` fails, or if the input is not valid
+/// JSON, does not match what `T` expects, or has trailing data other than
+/// whitespace. Also returns an error once the input nests deeper than the
+/// configuration's `max_depth`, or needs more stack than its `stack_budget`
+/// allows.
+pub fn from_reader_with_config<R, T>(rdr: R, config: Config) -> serde_json::Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    from_trait(IoRead::new(rdr), config)
+}
+
+/// Serialize the given data structure as JSON into the I/O stream, growing
+/// the stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if writing to `writer` fails, if `T`'s `Serialize` impl
+/// fails, or if `T` contains a map with non-string keys.
+pub fn to_writer<W, T>(writer: W, value: &T) -> serde_json::Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_with_config(writer, value, Config::default())
+}
+
+/// Serialize the given data structure as JSON into the I/O stream, using the
+/// given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if writing to `writer` fails, if `T`'s `Serialize` impl
+/// fails, or if `T` contains a map with non-string keys. Also returns an error
+/// once the value nests deeper than the configuration's `max_depth`, or needs
+/// more stack than its `stack_budget` allows.
+pub fn to_writer_with_config<W, T>(writer: W, value: &T, config: Config) -> serde_json::Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = serde_json::Serializer::new(writer);
+    value.serialize(crate::Serializer::with_config(&mut serializer, config))
+}
+
+/// Serialize the given data structure as pretty-printed JSON into the I/O
+/// stream, growing the stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if writing to `writer` fails, if `T`'s `Serialize` impl
+/// fails, or if `T` contains a map with non-string keys.
+pub fn to_writer_pretty<W, T>(writer: W, value: &T) -> serde_json::Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    to_writer_pretty_with_config(writer, value, Config::default())
+}
+
+/// Serialize the given data structure as pretty-printed JSON into the I/O
+/// stream, using the given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if writing to `writer` fails, if `T`'s `Serialize` impl
+/// fails, or if `T` contains a map with non-string keys. Also returns an error
+/// once the value nests deeper than the configuration's `max_depth`, or needs
+/// more stack than its `stack_budget` allows.
+pub fn to_writer_pretty_with_config<W, T>(
+    writer: W,
+    value: &T,
+    config: Config,
+) -> serde_json::Result<()>
+where
+    W: io::Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = serde_json::Serializer::pretty(writer);
+    value.serialize(crate::Serializer::with_config(&mut serializer, config))
+}
+
+/// Serialize the given data structure as a JSON byte vector, growing the
+/// stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys.
+pub fn to_vec<T>(value: &T) -> serde_json::Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    to_vec_with_config(value, Config::default())
+}
+
+/// Serialize the given data structure as a JSON byte vector, using the given
+/// stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys. Also returns an error once the value nests deeper than
+/// the configuration's `max_depth`, or needs more stack than its `stack_budget`
+/// allows.
+pub fn to_vec_with_config<T>(value: &T, config: Config) -> serde_json::Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_with_config(&mut writer, value, config)?;
+    Ok(writer)
+}
+
+/// Serialize the given data structure as a pretty-printed JSON byte vector,
+/// growing the stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys.
+pub fn to_vec_pretty<T>(value: &T) -> serde_json::Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    to_vec_pretty_with_config(value, Config::default())
+}
+
+/// Serialize the given data structure as a pretty-printed JSON byte vector,
+/// using the given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys. Also returns an error once the value nests deeper than
+/// the configuration's `max_depth`, or needs more stack than its `stack_budget`
+/// allows.
+pub fn to_vec_pretty_with_config<T>(value: &T, config: Config) -> serde_json::Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut writer = Vec::with_capacity(128);
+    to_writer_pretty_with_config(&mut writer, value, config)?;
+    Ok(writer)
+}
+
+/// Serialize the given data structure as a String of JSON, growing the stack
+/// as needed.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut value = Value::Null;
+/// for _ in 0..10000 {
+///     value = Value::Array(vec![value]);
+/// }
+///
+/// let json = serde_stacker::json::to_string(&value).unwrap();
+/// serde_stacker::json::drop_value(value);
+/// assert_eq!(json.len(), 10000 + "null".len() + 10000);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys.
+pub fn to_string<T>(value: &T) -> serde_json::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_with_config(value, Config::default())
+}
+
+/// Serialize the given data structure as a String of JSON, using the given
+/// stack growth configuration.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut value = Value::Null;
+/// for _ in 0..100 {
+///     value = Value::Array(vec![value]);
+/// }
+///
+/// let config = serde_stacker::Config::builder().max_depth(10).build().unwrap();
+/// let error = serde_stacker::json::to_string_with_config(&value, config).unwrap_err();
+/// assert_eq!(error.to_string(), "exceeded maximum nesting depth of 10");
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys. Also returns an error once the value nests deeper than
+/// the configuration's `max_depth`, or needs more stack than its `stack_budget`
+/// allows.
+pub fn to_string_with_config<T>(value: &T, config: Config) -> serde_json::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let vec = to_vec_with_config(value, config)?;
+    // serde_json does not emit invalid UTF-8.
+    let string = unsafe { String::from_utf8_unchecked(vec) };
+    Ok(string)
+}
+
+/// Serialize the given data structure as a pretty-printed String of JSON,
+/// growing the stack as needed.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys.
+pub fn to_string_pretty<T>(value: &T) -> serde_json::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_pretty_with_config(value, Config::default())
+}
+
+/// Serialize the given data structure as a pretty-printed String of JSON,
+/// using the given stack growth configuration.
+///
+/// # Errors
+///
+/// Returns an error if `T`'s `Serialize` impl fails, or if `T` contains a map
+/// with non-string keys. Also returns an error once the value nests deeper than
+/// the configuration's `max_depth`, or needs more stack than its `stack_budget`
+/// allows.
+pub fn to_string_pretty_with_config<T>(value: &T, config: Config) -> serde_json::Result<String>
+where
+    T: ?Sized + Serialize,
+{
+    let vec = to_vec_pretty_with_config(value, config)?;
+    // serde_json does not emit invalid UTF-8.
+    let string = unsafe { String::from_utf8_unchecked(vec) };
+    Ok(string)
+}
+
+/// Drop a `serde_json::Value` without recursion, regardless of how deeply its
+/// arrays and objects are nested.
+///
+/// ```
+/// use serde_json::Value;
+///
+/// let mut value = Value::Null;
+/// for _ in 0..100_000 {
+///     value = Value::Array(vec![value]);
+/// }
+///
+/// serde_stacker::json::drop_value(value);
+/// ```
+pub fn drop_value(value: Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Array(array) => stack.extend(array),
+            Value::Object(object) => stack.extend(object.into_iter().map(|(_key, value)| value)),
+            Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+        }
+    }
+}
+
+/// A `serde_json::Value` whose `Drop` impl is not recursive.
+///
+/// Serializing and deserializing a `SafeValue` goes through this crate's
+/// [`Serializer`][crate::Serializer] and [`Deserializer`][crate::Deserializer]
+/// adapters, so it is safe to embed in a larger data structure at any level.
+///
+/// ```
+/// use serde_stacker::json::SafeValue;
+///
+/// let mut json = String::new();
+/// for _ in 0..100_000 {
+///     json = format!("[{}]", json);
+/// }
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// deserializer.disable_recursion_limit();
+/// let value: SafeValue = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+/// assert!(value.is_array());
+/// ```
+#[derive(Default)]
+pub struct SafeValue(Value);
+
+impl SafeValue {
+    /// Wrap a `Value` to be dropped without recursion.
+    #[must_use]
+    pub fn new(value: Value) -> Self {
+        SafeValue(value)
+    }
+
+    /// Unwrap the underlying `Value`, which the caller then becomes responsible
+    /// for dropping carefully.
+    #[must_use]
+    pub fn into_inner(mut self) -> Value {
+        mem::take(&mut self.0)
+    }
+}
+
+impl Drop for SafeValue {
+    fn drop(&mut self) {
+        drop_value(mem::take(&mut self.0));
+    }
+}
+
+impl From<Value> for SafeValue {
+    fn from(value: Value) -> Self {
+        SafeValue(value)
+    }
+}
+
+impl Deref for SafeValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SafeValue {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Serialize for SafeValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let param = Param::top_level(Config::default());
+        self.0
+            .serialize(crate::Serializer::with_param(serializer, param))
+    }
+}
+
+impl<'de> Deserialize<'de> for SafeValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let param = Param::top_level(Config::default());
+        Value::deserialize(crate::Deserializer::with_param(deserializer, param)).map(SafeValue)
+    }
+}
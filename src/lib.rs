@@ -10,7 +10,10 @@
 //!
 //! Be aware that you may need to protect against other recursive operations
 //! outside of serialization and deserialization when working with deeply nested
-//! data, including, but not limited to, Display and Debug and Drop impls.
+//! data, including, but not limited to, Display and Debug and Drop impls. For
+//! Debug, see [`debug`] which formats any serializable value without
+//! recursing on the current stack, and similarly [`eq`], [`hash`] and
+//! [`deep_clone`] for `PartialEq`, `Hash` and `Clone`.
 //!
 //! # Deserialization example
 //!
@@ -74,15 +77,136 @@
 //!     }
 //! }
 //! ```
+//!
+//! # Tracing
+//!
+//! With the `tracing` feature enabled, the adapters emit [`tracing`] events
+//! with target `serde_stacker`: a debug event each time a new stack segment is
+//! allocated or reused from the pool, a warning when `max_depth` or
+//! `stack_budget` is exceeded, and a debug event summarizing the maximum depth,
+//! segments allocated and reused, and peak stack bytes when a top-level call
+//! through an adapter finishes.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+//!
+//! # No-std support
+//!
+//! The `std` feature is enabled by default. Without it, this crate supports
+//! `no_std` targets that have `alloc`. Growing the stack is not possible
+//! there, so the adapters instead rely on [`Config::max_depth`], which
+//! defaults to 128, and fail with an error on input nested any deeper. Error
+//! annotation, `Stats`, and `GrowthObserver` are only available with `std`.
 
-#![doc(html_root_url = "https://docs.rs/serde_stacker/0.1.14")]
+#![doc(html_root_url = "https://docs.rs/serde_stacker/0.2.0")]
+#![no_std]
+#![cfg_attr(docsrs, feature(doc_cfg))]
 #![allow(clippy::elidable_lifetime_names, clippy::needless_lifetimes)]
 
+extern crate alloc;
 extern crate serde_core as serde;
+#[cfg(feature = "std")]
+extern crate std;
 
+mod config;
 mod de;
+mod debug;
+mod event;
+#[cfg(feature = "std")]
+mod observer;
 mod param;
+mod path;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod pool;
 mod ser;
+#[cfg(feature = "std")]
+mod stack;
+mod stacked;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "tracing")]
+mod trace;
+mod transcode;
+mod value;
+
+pub mod field;
+#[cfg(feature = "serde_json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde_json")))]
+pub mod json;
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub mod thread;
 
+pub use crate::config::{Config, ConfigBuilder, ConfigError};
 pub use crate::de::Deserializer;
+pub use crate::debug::debug;
+pub use crate::event::{deep_clone, eq, hash};
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use crate::observer::{Growth, GrowthObserver};
 pub use crate::ser::Serializer;
+pub use crate::stacked::{Stacked, StackedSeed};
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use crate::stats::Stats;
+pub use crate::transcode::{transcode, transcode_with_config};
+pub use crate::value::Value;
+#[cfg(feature = "derive")]
+#[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
+/// ```
+/// use serde_stacker::StackSafe;
+///
+/// #[derive(StackSafe)]
+/// enum Expr {
+///     Lit(i64),
+///     Neg(Box<Expr>),
+///     Add(Box<Expr>, Box<Expr>),
+///     Call {
+///         name: String,
+///         args: Vec<Expr>,
+///     },
+/// }
+///
+/// let mut json = r#"{"Lit":1}"#.to_owned();
+/// for _ in 0..10000 {
+///     json = format!(r#"{{"Neg":{}}}"#, json);
+/// }
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// deserializer.disable_recursion_limit();
+/// let expr: Expr = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+///
+/// // Only Serialize and Deserialize are stack safe. Dropping is still
+/// // recursive, so take the value apart without recursing.
+/// let mut stack = vec![expr];
+/// while let Some(expr) = stack.pop() {
+///     match expr {
+///         Expr::Lit(_) => {}
+///         Expr::Neg(e) => stack.push(*e),
+///         Expr::Add(a, b) => stack.extend([*a, *b]),
+///         Expr::Call { args, .. } => stack.extend(args),
+///     }
+/// }
+/// ```
+pub use serde_stacker_derive::StackSafe;
+
+/// Whether the adapters are able to grow the stack on this platform.
+///
+/// If not, the [`Deserializer`] and [`Serializer`] adapters still enforce
+/// [`Config::max_depth`] and [`Config::stack_budget`], but otherwise run on the
+/// current stack like the underlying deserializer or serializer would. The
+/// functions in `serde_stacker::thread` can be used instead
+/// to get a large stack in a way that works on any platform that supports
+/// threads.
+///
+/// Always false without the `std` feature.
+#[must_use]
+pub fn is_growth_supported() -> bool {
+    #[cfg(feature = "std")]
+    return crate::stack::is_supported();
+    #[cfg(not(feature = "std"))]
+    return false;
+}
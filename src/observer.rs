@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+/// Callbacks invoked when the adapters move onto a new stack segment and when
+/// they leave it again.
+///
+/// Attach an observer to a [`Deserializer`][crate::Deserializer] or
+/// [`Serializer`][crate::Serializer] using their `with_observer` method. Both
+/// callbacks run on the new segment. The exit callback is skipped if the
+/// level of nesting panics.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_json::Value;
+/// use serde_stacker::{Growth, GrowthObserver};
+///
+/// struct Warn;
+///
+/// impl GrowthObserver for Warn {
+///     fn segment_entered(&self, growth: &Growth) {
+///         eprintln!("allocated stack segment at depth {}", growth.depth());
+///     }
+/// }
+///
+/// # fn main() -> serde_json::Result<()> {
+/// let mut deserializer = serde_json::Deserializer::from_str("[[], [[]]]");
+/// let deserializer = serde_stacker::Deserializer::new(&mut deserializer).with_observer(Warn);
+/// let value = Value::deserialize(deserializer)?;
+/// # let _ = value;
+/// # Ok(())
+/// # }
+/// ```
+pub trait GrowthObserver: Send + Sync {
+    /// Called at the start of running on a new stack segment, whether newly
+    /// allocated or reused from the thread's pool.
+    fn segment_entered(&self, growth: &Growth) {
+        let _ = growth;
+    }
+
+    /// Called when done running on the stack segment, just before returning
+    /// to the previous one.
+    fn segment_exited(&self, growth: &Growth) {
+        let _ = growth;
+    }
+}
+
+impl<O> GrowthObserver for Arc<O>
+where
+    O: ?Sized + GrowthObserver,
+{
+    fn segment_entered(&self, growth: &Growth) {
+        (**self).segment_entered(growth);
+    }
+
+    fn segment_exited(&self, growth: &Growth) {
+        (**self).segment_exited(growth);
+    }
+}
+
+/// Details of a new stack segment, passed to a [`GrowthObserver`].
+#[derive(Copy, Clone, Debug)]
+pub struct Growth {
+    pub(crate) depth: usize,
+    pub(crate) segments: usize,
+    pub(crate) stack_size: usize,
+}
+
+impl Growth {
+    /// Nesting depth of the sequence, map, enum, or value that did not fit in
+    /// the remaining stack.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Number of stack segments allocated by the adapters on this thread that
+    /// are in use, including this one.
+    #[must_use]
+    pub fn segments(&self) -> usize {
+        self.segments
+    }
+
+    /// Size in bytes of the new segment.
+    #[must_use]
+    pub fn stack_size(&self) -> usize {
+        self.stack_size
+    }
+}
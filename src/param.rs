@@ -1,23 +1,300 @@
-#[derive(Copy, Clone)]
+use crate::config::Config;
+#[cfg(feature = "std")]
+use crate::observer::{Growth, GrowthObserver};
+#[cfg(feature = "std")]
+use crate::path::Annotate;
+use crate::path::Segment;
+#[cfg(feature = "std")]
+use crate::stats::Stats;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use core::fmt::{self, Display};
+use serde::de;
+
+#[derive(Clone)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
 pub struct Param {
-    pub red_zone: usize,
-    pub stack_size: usize,
+    pub config: Config,
+    // Number of enclosing sequences, maps, and enums.
+    pub depth: usize,
+    #[cfg(feature = "std")]
+    pub stats: Option<Stats>,
+    #[cfg(feature = "std")]
+    pub observer: Option<Arc<dyn GrowthObserver>>,
+    // Present if errors are being annotated with depth or path.
+    #[cfg(feature = "std")]
+    pub annotate: Option<Arc<Annotate>>,
+    // Whether the value being deserialized is a map key or variant name to be
+    // recorded in the path.
+    pub key: bool,
+    #[cfg(feature = "tracing")]
+    pub summary: Option<Arc<crate::trace::Summary>>,
 }
 
-impl Default for Param {
-    fn default() -> Self {
+impl Param {
+    pub fn new(config: Config) -> Self {
         Param {
-            red_zone: 64 * 1024,
-            stack_size: 2 * 1024 * 1024,
+            config,
+            depth: 0,
+            #[cfg(feature = "std")]
+            stats: None,
+            #[cfg(feature = "std")]
+            observer: None,
+            #[cfg(feature = "std")]
+            annotate: None,
+            key: false,
+            #[cfg(feature = "tracing")]
+            summary: None,
+        }
+    }
+
+    // Parameters for a new top-level call into the adapters, as opposed to a
+    // nested one, which shares its caller's Param.
+    pub fn top_level(config: Config) -> Self {
+        Param {
+            #[cfg(feature = "std")]
+            annotate: if config.annotate_errors() || config.track_path() {
+                Some(Arc::new(Annotate::new(
+                    config.annotate_errors(),
+                    config.track_path(),
+                )))
+            } else {
+                None
+            },
+            ..Param::new(config)
+        }
+    }
+
+    // Parameters for a top-level call made directly by user code, whose
+    // measurements are reported through tracing once it is done. Wrappers
+    // such as Stacked, which are usually nested inside a larger call, use
+    // top_level instead so as not to report one summary per value.
+    pub fn summarized(
+        config: Config,
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] operation: &'static str,
+    ) -> Self {
+        Param {
+            #[cfg(feature = "tracing")]
+            summary: crate::trace::Summary::new(operation),
+            ..Param::top_level(config)
+        }
+    }
+
+    // Parameters for one level deeper of nesting, or an error if that would
+    // exceed the configured max_depth.
+    pub fn nest(self) -> Result<Self, LimitExceeded> {
+        let depth = self.depth + 1;
+        match self.config.max_depth() {
+            Some(max_depth) if depth > max_depth => {
+                Err(Param::exceeded(self.depth, LimitExceeded::Depth(max_depth)))
+            }
+            _ => {
+                #[cfg(feature = "std")]
+                if let Some(stats) = &self.stats {
+                    stats.record_depth(depth);
+                }
+                #[cfg(feature = "tracing")]
+                if let Some(summary) = &self.summary {
+                    summary.stats.record_depth(depth);
+                }
+                Ok(Param {
+                    depth,
+                    key: false,
+                    ..self
+                })
+            }
         }
     }
+
+    // Without std there is no way to grow the stack, leaving max_depth as the
+    // only protection against overflow.
+    #[cfg(not(feature = "std"))]
+    #[allow(clippy::unnecessary_wraps)] // same signature as with std
+    pub fn grow<R>(&self, f: impl FnOnce(Self) -> R) -> Result<R, LimitExceeded> {
+        Ok(f(self.clone()))
+    }
+
+    // Equivalent to stacker::maybe_grow, except that allocating a new segment
+    // fails if the segments in use would add up to more than stack_budget, and
+    // segments may come from the thread's pool.
+    //
+    // Independent adapters nested inside one another, such as Stacked fields
+    // or the functions in crate::field, each start from a fresh Param. So that
+    // the outermost stack_budget still holds, the segments in use are counted
+    // across every adapter on this thread, and f runs subject to this
+    // adapter's stack_budget in addition to that of any enclosing adapter.
+    #[cfg(feature = "std")]
+    pub fn grow<R>(&self, f: impl FnOnce(Self) -> R) -> Result<R, LimitExceeded> {
+        let f = |param| crate::stack::with_budget(self.config.stack_budget(), || f(param));
+        let enough_space = match crate::stack::remaining_stack() {
+            Some(remaining) => remaining >= self.config.red_zone(),
+            None => false,
+        };
+        if enough_space {
+            return Ok(f(self.clone()));
+        }
+        let stack_size = self.config.stack_size();
+        let in_use = crate::stack::in_use();
+        let segments = in_use.segments + 1;
+        let stack_bytes = in_use.bytes.saturating_add(stack_size);
+        if let Some(stack_budget) = crate::stack::budget(self.config.stack_budget()) {
+            if stack_bytes > stack_budget {
+                return Err(Param::exceeded(
+                    self.depth,
+                    LimitExceeded::StackBudget(stack_budget),
+                ));
+            }
+        }
+        let segment_pool = self.config.segment_pool();
+        let param = self.clone();
+        Ok(crate::stack::grow(stack_size, segment_pool, |reused| {
+            if let Some(stats) = &param.stats {
+                stats.record_segment(stack_bytes, reused);
+            }
+            #[cfg(feature = "tracing")]
+            {
+                crate::trace::segment_entered(param.depth, segments, stack_size, reused);
+                if let Some(summary) = &param.summary {
+                    summary.stats.record_segment(stack_bytes, reused);
+                }
+            }
+            let observer = match &param.observer {
+                Some(observer) => observer.clone(),
+                None => return f(param),
+            };
+            let growth = Growth {
+                depth: param.depth,
+                segments,
+                stack_size,
+            };
+            observer.segment_entered(&growth);
+            let ret = f(param);
+            observer.segment_exited(&growth);
+            ret
+        }))
+    }
+
+    fn exceeded(
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] depth: usize,
+        error: LimitExceeded,
+    ) -> LimitExceeded {
+        #[cfg(feature = "tracing")]
+        crate::trace::limit_exceeded(depth, &error);
+        error
+    }
 }
 
+#[cfg(feature = "std")]
 impl Param {
-    pub fn new(red_zone: usize, stack_size: usize) -> Self {
+    // Annotates the error, if enabled, with the current depth and path, unless
+    // it is one that already received an annotation at a deeper level.
+    pub fn annotate<T, E>(&self, result: Result<T, E>) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        let Some(annotate) = &self.annotate else {
+            return result;
+        };
+        match result {
+            Ok(value) => {
+                annotate.reset();
+                Ok(value)
+            }
+            Err(error) => match annotate.error(self.depth) {
+                Some(annotation) => {
+                    let message = error.to_string();
+                    let message = crate::path::strip_position(&message);
+                    Err(E::custom(format_args!("{message} ({annotation})")))
+                }
+                None => Err(error),
+            },
+        }
+    }
+
+    // Called at the start of each SeqAccess, MapAccess, and EnumAccess call.
+    // If an error was annotated before this, the visitor making the call has
+    // recovered from it, and the next error needs its own annotation.
+    pub fn start_access(&self) {
+        if let Some(annotate) = &self.annotate {
+            annotate.reset();
+        }
+    }
+
+    pub fn push_path(&self, segment: Segment) {
+        if let Some(annotate) = &self.annotate {
+            annotate.push(segment);
+        }
+    }
+
+    pub fn pop_path(&self) {
+        if let Some(annotate) = &self.annotate {
+            annotate.pop();
+        }
+    }
+
+    // Parameters for deserializing a map key or enum variant name, which will
+    // be recorded as the last segment of the path.
+    pub fn key(&self) -> Self {
+        let key = match &self.annotate {
+            Some(annotate) => annotate.tracks_path(),
+            None => false,
+        };
         Param {
-            red_zone,
-            stack_size,
+            key,
+            ..self.clone()
+        }
+    }
+
+    pub fn record_key(&self, name: impl Display) {
+        if self.key {
+            if let Some(annotate) = &self.annotate {
+                annotate.set_name(name.to_string());
+            }
+        }
+    }
+}
+
+// Error annotation relies on std::sync::Mutex, so without std it is off.
+#[cfg(not(feature = "std"))]
+#[allow(clippy::unused_self)]
+impl Param {
+    pub fn annotate<T, E>(&self, result: Result<T, E>) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        result
+    }
+
+    pub fn start_access(&self) {}
+
+    pub fn push_path(&self, _segment: Segment) {}
+
+    pub fn pop_path(&self) {}
+
+    pub fn key(&self) -> Self {
+        self.clone()
+    }
+
+    pub fn record_key(&self, _name: impl Display) {}
+}
+
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub enum LimitExceeded {
+    Depth(usize),
+    StackBudget(usize),
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Depth(max_depth) => {
+                write!(formatter, "exceeded maximum nesting depth of {max_depth}")
+            }
+            LimitExceeded::StackBudget(stack_budget) => {
+                write!(formatter, "exceeded stack budget of {stack_budget} bytes")
+            }
         }
     }
 }
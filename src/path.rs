@@ -0,0 +1,152 @@
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// State for annotating deserialization errors, shared by every level of one
+// top-level call.
+#[cfg(feature = "std")]
+pub struct Annotate {
+    depth: bool,
+    // Set while an error which has already been annotated is propagating
+    // outward through enclosing levels.
+    annotated: AtomicBool,
+    // Location of the value currently being deserialized, if path tracking is
+    // enabled. This is kept on the heap rather than in the adapter's stack
+    // frames so that it can be rendered at any depth without recursing.
+    path: Option<Mutex<Vec<Segment>>>,
+}
+
+pub enum Segment {
+    Index(usize),
+    // Map key or enum variant name, once known.
+    Name(Option<String>),
+}
+
+#[cfg(feature = "std")]
+impl Annotate {
+    pub fn new(depth: bool, path: bool) -> Self {
+        Annotate {
+            depth,
+            annotated: AtomicBool::new(false),
+            path: if path {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn tracks_path(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub fn push(&self, segment: Segment) {
+        if let Some(mut path) = self.path() {
+            path.push(segment);
+        }
+    }
+
+    pub fn pop(&self) {
+        if let Some(mut path) = self.path() {
+            path.pop();
+        }
+    }
+
+    pub fn set_name(&self, name: String) {
+        if let Some(mut path) = self.path() {
+            if let Some(Segment::Name(segment @ None)) = path.last_mut() {
+                *segment = Some(name);
+            }
+        }
+    }
+
+    // Returns the annotation for a new error, or None if the error is one
+    // that was already annotated at a deeper level.
+    pub fn error(&self, depth: usize) -> Option<String> {
+        if self.annotated.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        let mut annotation = String::from("at ");
+        if let Some(path) = self.path() {
+            annotation.push_str("path ");
+            annotation.push_str(&Path(&path).to_string());
+            if self.depth {
+                annotation.push_str(", ");
+            }
+        }
+        if self.depth {
+            annotation.push_str("nesting depth ");
+            annotation.push_str(&depth.to_string());
+        }
+        Some(annotation)
+    }
+
+    // Called on success, and as each SeqAccess, MapAccess, or EnumAccess call
+    // starts. Either means any error seen previously has been handled, by a
+    // visitor which recovered from it, and the next one is new.
+    pub fn reset(&self) {
+        self.annotated.store(false, Ordering::Relaxed);
+    }
+
+    fn path(&self) -> Option<MutexGuard<'_, Vec<Segment>>> {
+        let path = self.path.as_ref()?;
+        Some(path.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+// Removes the position from the end of an error message in the form used by
+// serde_json, as in `... at line 1 column 13`. Serde_json adds the position to
+// custom errors as they propagate outward, so if it were kept, the annotated
+// error would show the original position in the middle of its message and a
+// second, different one at the end.
+#[cfg(feature = "std")]
+pub fn strip_position(message: &str) -> &str {
+    fn split(message: &str) -> Option<&str> {
+        let (rest, column) = message.rsplit_once(" column ")?;
+        let (rest, line) = rest.rsplit_once(" at line ")?;
+        let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        if is_number(line) && is_number(column) {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+    split(message).unwrap_or(message)
+}
+
+struct Path<'a>(&'a [Segment]);
+
+impl<'a> Display for Path<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("$")?;
+        for segment in self.0 {
+            match segment {
+                Segment::Index(index) => write!(formatter, "[{index}]")?,
+                Segment::Name(Some(name)) if is_identifier(name) => {
+                    write!(formatter, ".{name}")?;
+                }
+                Segment::Name(Some(name)) => write!(formatter, "[{name:?}]")?,
+                Segment::Name(None) => formatter.write_str(".?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch == '_' || ch.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
@@ -0,0 +1,405 @@
+//! Running serialization and deserialization on a pool of big-stack threads.
+//!
+//! A [`StackPool`] owns a fixed number of threads, each with a stack of
+//! [`Config::thread_stack_size`] bytes, and runs work on them through this
+//! crate's [`Deserializer`][crate::Deserializer] and
+//! [`Serializer`][crate::Serializer] adapters. This keeps deep recursion, and
+//! any stack segments allocated for it, off the calling thread, such as the
+//! worker threads of an async runtime.
+//!
+//! Each operation comes in a blocking flavor, which waits for the result, and
+//! an `_async` flavor, which returns a [`Task`] that can be awaited.
+//!
+//! ```
+//! use serde_json::Value;
+//! use serde_stacker::pool::StackPool;
+//! use serde_stacker::Config;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let pool = StackPool::new(2, Config::default())?;
+//!
+//! let json = b"[[[[]]]]".to_vec();
+//! let value: Value = pool.deserialize(move |input| {
+//!     let mut deserializer = serde_json::Deserializer::from_slice(&json);
+//!     deserializer.disable_recursion_limit();
+//!     input.deserialize(&mut deserializer)
+//! })?;
+//!
+//! let json = pool.serialize(value, |output| {
+//!     let mut json = Vec::new();
+//!     output.serialize(&mut serde_json::Serializer::new(&mut json))?;
+//!     Ok::<_, serde_json::Error>(json)
+//! })?;
+//! assert_eq!(json, b"[[[[]]]]");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use serde::de::{self, DeserializeOwned};
+use serde::ser::{self, Serialize};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Fixed-size pool of threads with large stacks on which to serialize and
+/// deserialize.
+///
+/// Dropping the pool waits for work that was already submitted to finish.
+pub struct StackPool {
+    config: Config,
+    sender: Option<Mutex<mpsc::Sender<Job>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl StackPool {
+    /// Spawn `threads` threads, each with a stack of
+    /// [`Config::thread_stack_size`] bytes, which will run work through the
+    /// adapters with the given configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the threads could not be spawned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is 0.
+    pub fn new(threads: usize, config: Config) -> io::Result<Self> {
+        assert!(threads > 0, "StackPool requires at least one thread");
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut pool = StackPool {
+            config,
+            sender: Some(Mutex::new(sender)),
+            threads: Vec::with_capacity(threads),
+        };
+        for i in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            let thread = thread::Builder::new()
+                .name(format!("serde_stacker-{i}"))
+                .stack_size(config.thread_stack_size())
+                .spawn(move || loop {
+                    let job = match lock(&receiver).recv() {
+                        Ok(job) => job,
+                        Err(mpsc::RecvError) => return,
+                    };
+                    job();
+                })?;
+            pool.threads.push(thread);
+        }
+        Ok(pool)
+    }
+
+    /// The configuration used for the adapters.
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Run `input_fn` on one of the pool's threads and wait for the result.
+    ///
+    /// `input_fn` is expected to set up a deserializer for its input and pass
+    /// it to [`Input::deserialize`], which wraps it in this crate's
+    /// [`Deserializer`][crate::Deserializer]. A panic in `input_fn` is
+    /// propagated to the caller.
+    ///
+    /// ```
+    /// # use serde_stacker::pool::StackPool;
+    /// # use serde_stacker::Config;
+    /// #
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let pool = StackPool::new(1, Config::default())?;
+    /// let value = pool.deserialize::<Vec<u8>, _>(|input| {
+    ///     let mut deserializer = serde_json::Deserializer::from_str("[1, 2]");
+    ///     input.deserialize(&mut deserializer)
+    /// })?;
+    /// assert_eq!(value, [1, 2]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the error returned by `input_fn`.
+    pub fn deserialize<T, E>(
+        &self,
+        input_fn: impl FnOnce(Input<T>) -> Result<T, E> + Send + 'static,
+    ) -> Result<T, E>
+    where
+        T: DeserializeOwned + Send + 'static,
+        E: Send + 'static,
+    {
+        self.deserialize_async(input_fn).wait()
+    }
+
+    /// Like [`deserialize`][StackPool::deserialize], but returns a [`Task`]
+    /// which resolves to the result instead of blocking the current thread.
+    pub fn deserialize_async<T, E>(
+        &self,
+        input_fn: impl FnOnce(Input<T>) -> Result<T, E> + Send + 'static,
+    ) -> Task<Result<T, E>>
+    where
+        T: DeserializeOwned + Send + 'static,
+        E: Send + 'static,
+    {
+        let input = Input {
+            config: self.config,
+            marker: PhantomData,
+        };
+        self.spawn(move || input_fn(input))
+    }
+
+    /// Run `output_fn` on one of the pool's threads and wait for the result.
+    ///
+    /// `output_fn` is expected to set up a serializer for its output and pass
+    /// it to [`Output::serialize`], which serializes `value` through this
+    /// crate's [`Serializer`][crate::Serializer]. The value is dropped on the
+    /// pool's thread as well, which matters for types whose `Drop` impl is
+    /// recursive. A panic in `output_fn` is propagated to the caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error returned by `output_fn`.
+    pub fn serialize<T, R, E>(
+        &self,
+        value: T,
+        output_fn: impl FnOnce(Output<T>) -> Result<R, E> + Send + 'static,
+    ) -> Result<R, E>
+    where
+        T: Serialize + Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+    {
+        self.serialize_async(value, output_fn).wait()
+    }
+
+    /// Like [`serialize`][StackPool::serialize], but returns a [`Task`] which
+    /// resolves to the result instead of blocking the current thread.
+    pub fn serialize_async<T, R, E>(
+        &self,
+        value: T,
+        output_fn: impl FnOnce(Output<T>) -> Result<R, E> + Send + 'static,
+    ) -> Task<Result<R, E>>
+    where
+        T: Serialize + Send + 'static,
+        R: Send + 'static,
+        E: Send + 'static,
+    {
+        let output = Output {
+            config: self.config,
+            value,
+        };
+        self.spawn(move || output_fn(output))
+    }
+
+    fn spawn<R>(&self, f: impl FnOnce() -> R + Send + 'static) -> Task<R>
+    where
+        R: Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                result: None,
+                closed: false,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        let sender = Sender {
+            shared: Arc::clone(&shared),
+        };
+        let job: Job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            sender.send(result);
+        });
+        // The receiver is only dropped by the pool's threads exiting, which
+        // happens after the sender is dropped.
+        let _ = lock(self.sender.as_ref().unwrap()).send(job);
+        Task { shared }
+    }
+}
+
+impl Drop for StackPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Debug for StackPool {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("StackPool")
+            .field("config", &self.config)
+            .field("threads", &self.threads.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Handle given to the `input_fn` of [`StackPool::deserialize`].
+pub struct Input<T> {
+    config: Config,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Input<T>
+where
+    T: DeserializeOwned,
+{
+    /// Deserialize a `T` from the given deserializer, wrapped in this crate's
+    /// [`Deserializer`][crate::Deserializer] with the pool's configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from deserialization.
+    pub fn deserialize<'de, D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        T::deserialize(crate::Deserializer::with_config(deserializer, self.config))
+    }
+}
+
+/// Handle given to the `output_fn` of [`StackPool::serialize`].
+pub struct Output<T> {
+    config: Config,
+    value: T,
+}
+
+impl<T> Output<T>
+where
+    T: Serialize,
+{
+    /// The value to be serialized.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Serialize the value into the given serializer, wrapped in this crate's
+    /// [`Serializer`][crate::Serializer] with the pool's configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from serialization.
+    pub fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.value
+            .serialize(crate::Serializer::with_config(serializer, self.config))
+    }
+}
+
+/// Result of work submitted to a [`StackPool`], which can be either waited
+/// for or awaited.
+///
+/// If the work panicked, the panic is resumed on the thread that waits for or
+/// polls the task.
+#[must_use = "tasks do nothing unless waited for or awaited"]
+pub struct Task<R> {
+    shared: Arc<Shared<R>>,
+}
+
+impl<R> Task<R> {
+    /// Block the current thread until the result is available.
+    pub fn wait(self) -> R {
+        let mut state = lock(&self.shared.state);
+        loop {
+            if let Some(result) = state.take() {
+                return result;
+            }
+            state = self
+                .shared
+                .ready
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl<R> Future for Task<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<R> {
+        let mut state = lock(&self.shared.state);
+        if let Some(result) = state.take() {
+            return Poll::Ready(result);
+        }
+        match &state.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl<R> Debug for Task<R> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("Task")
+    }
+}
+
+// One-shot channel carrying the result of a job back to its Task.
+struct Shared<R> {
+    state: Mutex<State<R>>,
+    ready: Condvar,
+}
+
+struct State<R> {
+    result: Option<thread::Result<R>>,
+    // Set if the job was dropped without producing a result.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<R> State<R> {
+    fn take(&mut self) -> Option<R> {
+        match self.result.take() {
+            Some(Ok(ret)) => Some(ret),
+            Some(Err(panic)) => panic::resume_unwind(panic),
+            None if self.closed => panic!("StackPool thread exited without a result"),
+            None => None,
+        }
+    }
+}
+
+struct Sender<R> {
+    shared: Arc<Shared<R>>,
+}
+
+impl<R> Sender<R> {
+    fn send(&self, result: thread::Result<R>) {
+        lock(&self.shared.state).result = Some(result);
+    }
+}
+
+impl<R> Drop for Sender<R> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = lock(&self.shared.state);
+            state.closed = true;
+            state.waker.take()
+        };
+        self.shared.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -1,30 +1,72 @@
+use crate::config::Config;
+#[cfg(feature = "std")]
+use crate::observer::GrowthObserver;
 use crate::param::Param;
+#[cfg(feature = "std")]
+use crate::stats::Stats;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use core::fmt::Display;
 use serde::ser;
-use std::fmt::Display;
 
 /// Serializer adapter that avoids stack overflows by dynamically growing the
 /// stack.
 ///
 /// At each level of nested serialization, the adapter will check whether it is
 /// within `red_zone` bytes of the end of the stack. If so, it will allocate a
-/// new stack of size `stack_size` on which to continue deserialization.
+/// new stack of size `stack_size` on which to continue serialization. See
+/// [`Config`] for these and other settings.
 pub struct Serializer<S> {
     pub ser: S,
-    pub red_zone: usize,
-    pub stack_size: usize,
+    param: Param,
 }
 
 impl<S> Serializer<S> {
-    /// Build a serializer adapter with reasonable default `red_zone` (64 KB)
-    /// and `stack_size` (2 MB).
+    /// Build a serializer adapter with the default [`Config`].
     pub fn new(serializer: S) -> Self {
-        let default_param = Param::default();
+        Self::with_config(serializer, Config::default())
+    }
+
+    /// Build a serializer adapter with the given configuration.
+    pub fn with_config(serializer: S, config: Config) -> Self {
+        Serializer {
+            ser: serializer,
+            param: Param::summarized(config, "serialize"),
+        }
+    }
+
+    // Adapter for use inside of a Serialize or Deserialize impl, which may be
+    // running within an enclosing adapter already.
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn with_param(serializer: S, param: Param) -> Self {
         Serializer {
             ser: serializer,
-            red_zone: default_param.red_zone,
-            stack_size: default_param.stack_size,
+            param,
         }
     }
+
+    /// Record how deep the output nests and how much stack it uses into the
+    /// given [`Stats`].
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[must_use]
+    pub fn with_stats(mut self, stats: &Stats) -> Self {
+        self.param.stats = Some(stats.clone());
+        self
+    }
+
+    /// Notify the given [`GrowthObserver`] each time a new stack segment is
+    /// entered and exited.
+    #[cfg(feature = "std")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    #[must_use]
+    pub fn with_observer<O>(mut self, observer: O) -> Self
+    where
+        O: GrowthObserver + 'static,
+    {
+        self.param.observer = Some(Arc::new(observer));
+        self
+    }
 }
 
 impl<S> ser::Serializer for Serializer<S>
@@ -114,8 +156,7 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        self.ser.serialize_some(&Serialize::new(value, param))
+        self.ser.serialize_some(&Serialize::new(value, self.param))
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
@@ -132,6 +173,7 @@ where
         variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
+        self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_unit_variant(name, variant_index, variant)
     }
@@ -144,9 +186,8 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
         self.ser
-            .serialize_newtype_struct(name, &Serialize::new(value, param))
+            .serialize_newtype_struct(name, &Serialize::new(value, self.param))
     }
 
     fn serialize_newtype_variant<T>(
@@ -159,7 +200,7 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser.serialize_newtype_variant(
             name,
             variant_index,
@@ -169,14 +210,14 @@ where
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_seq(len)
             .map(|ser| SerializeSeq::new(ser, param))
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_tuple(len)
             .map(|ser| SerializeTuple::new(ser, param))
@@ -187,7 +228,7 @@ where
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_tuple_struct(name, len)
             .map(|ser| SerializeTupleStruct::new(ser, param))
@@ -200,14 +241,14 @@ where
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_tuple_variant(name, variant_index, variant, len)
             .map(|ser| SerializeTupleVariant::new(ser, param))
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_map(len)
             .map(|ser| SerializeMap::new(ser, param))
@@ -218,7 +259,7 @@ where
         name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_struct(name, len)
             .map(|ser| SerializeStruct::new(ser, param))
@@ -231,7 +272,7 @@ where
         variant: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         self.ser
             .serialize_struct_variant(name, variant_index, variant, len)
             .map(|ser| SerializeStructVariant::new(ser, param))
@@ -242,10 +283,10 @@ where
         I: IntoIterator,
         I::Item: ser::Serialize,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
+        let param = self.param.nest().map_err(ser::Error::custom)?;
         let iter = iter
             .into_iter()
-            .map(|item| SerializeSized::new(item, param));
+            .map(|item| SerializeSized::new(item, param.clone()));
         self.ser.collect_seq(iter)
     }
 
@@ -255,10 +296,13 @@ where
         V: ser::Serialize,
         I: IntoIterator<Item = (K, V)>,
     {
-        let param = Param::new(self.red_zone, self.stack_size);
-        let iter = iter
-            .into_iter()
-            .map(|(k, v)| (SerializeSized::new(k, param), SerializeSized::new(v, param)));
+        let param = self.param.nest().map_err(ser::Error::custom)?;
+        let iter = iter.into_iter().map(|(k, v)| {
+            (
+                SerializeSized::new(k, param.clone()),
+                SerializeSized::new(v, param.clone()),
+            )
+        });
         self.ser.collect_map(iter)
     }
 
@@ -300,7 +344,7 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.ser
-            .serialize_element(&Serialize::new(value, self.param))
+            .serialize_element(&Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -334,7 +378,7 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.ser
-            .serialize_element(&Serialize::new(value, self.param))
+            .serialize_element(&Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -367,7 +411,8 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        self.ser.serialize_field(&Serialize::new(value, self.param))
+        self.ser
+            .serialize_field(&Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -400,7 +445,8 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        self.ser.serialize_field(&Serialize::new(value, self.param))
+        self.ser
+            .serialize_field(&Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -433,14 +479,16 @@ where
     where
         T: ?Sized + ser::Serialize,
     {
-        self.ser.serialize_key(&Serialize::new(key, self.param))
+        self.ser
+            .serialize_key(&Serialize::new(key, self.param.clone()))
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + ser::Serialize,
     {
-        self.ser.serialize_value(&Serialize::new(value, self.param))
+        self.ser
+            .serialize_value(&Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -453,8 +501,8 @@ where
         V: ?Sized + ser::Serialize,
     {
         self.ser.serialize_entry(
-            &Serialize::new(key, self.param),
-            &Serialize::new(value, self.param),
+            &Serialize::new(key, self.param.clone()),
+            &Serialize::new(value, self.param.clone()),
         )
     }
 }
@@ -485,7 +533,7 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.ser
-            .serialize_field(key, &Serialize::new(value, self.param))
+            .serialize_field(key, &Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -523,7 +571,7 @@ where
         T: ?Sized + ser::Serialize,
     {
         self.ser
-            .serialize_field(key, &Serialize::new(value, self.param))
+            .serialize_field(key, &Serialize::new(value, self.param.clone()))
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -535,13 +583,18 @@ where
     }
 }
 
-struct Serialize<'a, T: ?Sized> {
+// Nested value passed to the inner serializer, which continues through the
+// adapter on a new stack segment if the current one is running low. Every
+// serialize_some, serialize_element, serialize_field and similar goes through
+// this, so nesting through any of them is protected, not only sequences and
+// maps.
+pub struct Serialize<'a, T: ?Sized> {
     value: &'a T,
     param: Param,
 }
 
 impl<'a, T: ?Sized> Serialize<'a, T> {
-    fn new(value: &'a T, param: Param) -> Self {
+    pub fn new(value: &'a T, param: Param) -> Self {
         Serialize { value, param }
     }
 }
@@ -554,14 +607,17 @@ where
     where
         S: ser::Serializer,
     {
-        ser::Serialize::serialize(
-            self.value,
-            Serializer {
-                ser: serializer,
-                red_zone: self.param.red_zone,
-                stack_size: self.param.stack_size,
-            },
-        )
+        self.param
+            .grow(|param| {
+                ser::Serialize::serialize(
+                    self.value,
+                    Serializer {
+                        ser: serializer,
+                        param,
+                    },
+                )
+            })
+            .map_err(ser::Error::custom)?
     }
 }
 
@@ -584,15 +640,16 @@ where
     where
         S: ser::Serializer,
     {
-        stacker::maybe_grow(self.param.red_zone, self.param.stack_size, || {
-            ser::Serialize::serialize(
-                &self.value,
-                Serializer {
-                    ser: serializer,
-                    red_zone: self.param.red_zone,
-                    stack_size: self.param.stack_size,
-                },
-            )
-        })
+        self.param
+            .grow(|param| {
+                ser::Serialize::serialize(
+                    &self.value,
+                    Serializer {
+                        ser: serializer,
+                        param,
+                    },
+                )
+            })
+            .map_err(ser::Error::custom)?
     }
 }
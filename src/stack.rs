@@ -0,0 +1,349 @@
+// Allocation of new stack segments for Param::grow.
+//
+// By default this defers to stacker, which maps a fresh segment every time and
+// unmaps it afterward. For input which repeatedly crosses the red zone at the
+// same depth, such as many siblings nested just deep enough to need a new
+// segment each, that amounts to a pair of mmap and munmap calls per sibling.
+// When Config::segment_pool is nonzero, segments are instead kept in a
+// thread-local pool after use, and reused by subsequent growth on the same
+// thread.
+//
+// Stacker has no way to be told about a pooled segment, so while running on
+// one, stacker::remaining_stack measures against whichever limit it knew
+// before. That is only safe if the answer comes out no larger than the space
+// actually left on the segment, which holds when stacker's limit lies at or
+// above the segment's base. Any segment for which that does not hold is passed
+// over, and if no segment qualifies, growth goes through stacker::grow
+// instead, so that code inside the adapters which calls stacker::maybe_grow
+// itself never overestimates the stack it has.
+
+use std::cell::Cell;
+use std::thread_local;
+
+thread_local! {
+    // Lowest usable address of the pooled segment currently running, if any.
+    // Stacker does not know about our segments, so while running on one of
+    // them, its own idea of the stack limit does not apply.
+    static LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+
+    // Segments entered through grow on this thread which have not yet been
+    // exited, across every adapter, nested or not, so that stack_budget covers
+    // them all.
+    static IN_USE: Cell<InUse> = const { Cell::new(InUse { segments: 0, bytes: 0 }) };
+
+    // Smallest stack_budget of the adapters currently running on this thread.
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+#[derive(Copy, Clone)]
+pub struct InUse {
+    pub segments: usize,
+    pub bytes: usize,
+}
+
+pub fn in_use() -> InUse {
+    IN_USE.with(Cell::get)
+}
+
+// The stack budget that applies to an adapter with the given stack_budget,
+// taking into account every adapter it is nested in.
+pub fn budget(stack_budget: Option<usize>) -> Option<usize> {
+    match (stack_budget, BUDGET.with(Cell::get)) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+// Runs f with the given stack_budget applying to any adapter nested within.
+pub fn with_budget<R>(stack_budget: Option<usize>, f: impl FnOnce() -> R) -> R {
+    if stack_budget.is_none() {
+        return f();
+    }
+    let _restore = RestoreBudget {
+        previous: BUDGET.with(|cell| cell.replace(budget(stack_budget))),
+    };
+    f()
+}
+
+struct RestoreBudget {
+    previous: Option<usize>,
+}
+
+impl Drop for RestoreBudget {
+    fn drop(&mut self) {
+        BUDGET.with(|cell| cell.set(self.previous));
+    }
+}
+
+// Whether stacker::grow actually switches to a new stack on this platform.
+// Where psm cannot switch stacks, stacker only does so on Windows (using
+// fibers), and elsewhere runs the callback on the current stack.
+pub fn is_supported() -> bool {
+    psm::psm_stack_manipulation! {
+        yes { true }
+        no { cfg!(all(windows, not(miri))) }
+    }
+}
+
+// Size of a page of memory, to which stack sizes are rounded. Where this
+// cannot be queried, 4 KB is assumed.
+pub fn page_size() -> usize {
+    #[cfg(unix)]
+    {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        usize::try_from(page_size).unwrap_or(4 * 1024)
+    }
+    #[cfg(not(unix))]
+    {
+        4 * 1024
+    }
+}
+
+pub fn remaining_stack() -> Option<usize> {
+    match LIMIT.with(Cell::get) {
+        Some(limit) => Some(imp::stack_pointer().saturating_sub(limit)),
+        None => stacker::remaining_stack(),
+    }
+}
+
+// Runs f on a new stack segment. The argument to f is whether the segment was
+// reused from the pool, as opposed to newly allocated.
+pub fn grow<R>(stack_size: usize, segment_pool: usize, f: impl FnOnce(bool) -> R) -> R {
+    let f = |reused| {
+        let _exit = ExitSegment::enter(stack_size);
+        f(reused)
+    };
+    if segment_pool == 0 || !imp::SUPPORTED {
+        return grow_unpooled(stack_size, f);
+    }
+    imp::grow(stack_size, segment_pool, f)
+}
+
+fn grow_unpooled<R>(stack_size: usize, f: impl FnOnce(bool) -> R) -> R {
+    let _restore = RestoreLimit::replace(None);
+    stacker::grow(stack_size, || f(false))
+}
+
+// Removes a segment from IN_USE when it is exited, including by panic.
+struct ExitSegment {
+    stack_size: usize,
+}
+
+impl ExitSegment {
+    fn enter(stack_size: usize) -> Self {
+        IN_USE.with(|cell| {
+            let in_use = cell.get();
+            cell.set(InUse {
+                segments: in_use.segments + 1,
+                bytes: in_use.bytes.saturating_add(stack_size),
+            });
+        });
+        ExitSegment { stack_size }
+    }
+}
+
+impl Drop for ExitSegment {
+    fn drop(&mut self) {
+        IN_USE.with(|cell| {
+            let in_use = cell.get();
+            cell.set(InUse {
+                segments: in_use.segments - 1,
+                bytes: in_use.bytes.saturating_sub(self.stack_size),
+            });
+        });
+    }
+}
+
+// Restores the previous LIMIT when the segment is exited, including by panic.
+struct RestoreLimit {
+    previous: Option<usize>,
+}
+
+impl RestoreLimit {
+    fn replace(limit: Option<usize>) -> Self {
+        RestoreLimit {
+            previous: LIMIT.with(|cell| cell.replace(limit)),
+        }
+    }
+}
+
+impl Drop for RestoreLimit {
+    fn drop(&mut self) {
+        LIMIT.with(|cell| cell.set(self.previous));
+    }
+}
+
+#[cfg(all(unix, not(target_os = "openbsd")))]
+psm::psm_stack_manipulation! {
+    yes {
+        mod imp {
+            use super::RestoreLimit;
+            use std::cell::RefCell;
+            use std::io;
+            use std::panic::{self, AssertUnwindSafe};
+            use std::ptr;
+            use std::thread_local;
+            use std::vec::Vec;
+
+            pub const SUPPORTED: bool = true;
+
+            thread_local! {
+                static POOL: RefCell<Vec<Segment>> = const { RefCell::new(Vec::new()) };
+            }
+
+            pub fn stack_pointer() -> usize {
+                psm::stack_pointer() as usize
+            }
+
+            pub fn grow<R>(stack_size: usize, segment_pool: usize, f: impl FnOnce(bool) -> R) -> R {
+                // Lower bound on the address stacker considers the limit of
+                // the stack. Its limit is exactly this address, unless it
+                // reports no stack remaining, in which case it may be higher.
+                let stacker_limit = match stacker::remaining_stack() {
+                    Some(remaining) => stack_pointer().saturating_sub(remaining),
+                    None => usize::MAX,
+                };
+                let stacker_safe = |segment: &Segment| segment.base as usize <= stacker_limit;
+
+                let pooled = POOL
+                    .try_with(|pool| {
+                        let mut pool = pool.borrow_mut();
+                        let index = pool.iter().position(|segment| {
+                            segment.stack_size == stack_size && stacker_safe(segment)
+                        })?;
+                        Some(pool.swap_remove(index))
+                    })
+                    .ok()
+                    .flatten();
+                let reused = pooled.is_some();
+                let segment = if let Some(segment) = pooled {
+                    segment
+                } else {
+                    let segment = Segment::new(stack_size);
+                    if !stacker_safe(&segment) {
+                        drop(segment);
+                        return super::grow_unpooled(stack_size, f);
+                    }
+                    segment
+                };
+
+                let mut f = Some(f);
+                let mut ret = None;
+                let restore = RestoreLimit::replace(Some(segment.base as usize));
+                // The callback must not unwind across the stack switch.
+                let panic = unsafe {
+                    psm::on_stack(segment.base, segment.usable_size, || {
+                        panic::catch_unwind(AssertUnwindSafe(|| {
+                            ret = Some(f.take().unwrap()(reused));
+                        }))
+                        .err()
+                    })
+                };
+                drop(restore);
+
+                let _ = POOL.try_with(|pool| {
+                    let mut pool = pool.borrow_mut();
+                    if pool.len() < segment_pool {
+                        pool.push(segment);
+                    }
+                });
+
+                if let Some(panic) = panic {
+                    panic::resume_unwind(panic);
+                }
+                ret.unwrap()
+            }
+
+            // A mapping containing a stack with a guard page on either side.
+            struct Segment {
+                mapping: *mut libc::c_void,
+                mapping_size: usize,
+                base: *mut u8,
+                usable_size: usize,
+                stack_size: usize,
+            }
+
+            impl Segment {
+                fn new(stack_size: usize) -> Self {
+                    let page_size = super::page_size();
+                    let pages = stack_size
+                        .checked_add(page_size - 1)
+                        .expect("unreasonably large stack requested")
+                        / page_size;
+                    let usable_size = pages.max(1) * page_size;
+                    let mapping_size = usable_size
+                        .checked_add(2 * page_size)
+                        .expect("unreasonably large stack requested");
+                    unsafe {
+                        let mapping = libc::mmap(
+                            ptr::null_mut(),
+                            mapping_size,
+                            libc::PROT_NONE,
+                            libc::MAP_PRIVATE | libc::MAP_ANON,
+                            -1,
+                            0,
+                        );
+                        assert_ne!(
+                            mapping,
+                            libc::MAP_FAILED,
+                            "mmap failed to allocate stack: {}",
+                            io::Error::last_os_error(),
+                        );
+                        let segment = Segment {
+                            mapping,
+                            mapping_size,
+                            base: mapping.cast::<u8>().add(page_size),
+                            usable_size,
+                            stack_size,
+                        };
+                        let result = libc::mprotect(
+                            segment.base.cast(),
+                            usable_size,
+                            libc::PROT_READ | libc::PROT_WRITE,
+                        );
+                        assert_ne!(
+                            result,
+                            -1,
+                            "mprotect failed: {}",
+                            io::Error::last_os_error(),
+                        );
+                        segment
+                    }
+                }
+            }
+
+            impl Drop for Segment {
+                fn drop(&mut self) {
+                    unsafe {
+                        libc::munmap(self.mapping, self.mapping_size);
+                    }
+                }
+            }
+        }
+    }
+
+    no {
+        mod imp {
+            pub use super::fallback::*;
+        }
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "openbsd"))))]
+mod imp {
+    pub use super::fallback::*;
+}
+
+#[allow(dead_code)]
+mod fallback {
+    pub const SUPPORTED: bool = false;
+
+    pub fn stack_pointer() -> usize {
+        unreachable!()
+    }
+
+    pub fn grow<R>(stack_size: usize, segment_pool: usize, f: impl FnOnce(bool) -> R) -> R {
+        let _ = segment_pool;
+        super::grow_unpooled(stack_size, f)
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::Config;
+use crate::param::Param;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use serde::de::{Deserialize, DeserializeSeed, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+/// Wrapper that serializes and deserializes its contents through this crate's
+/// [`Serializer`][crate::Serializer] and [`Deserializer`][crate::Deserializer]
+/// adapters, using the default [`Config`].
+///
+/// Embedding `Stacked<T>` in a larger data structure makes the `T` part of it
+/// safe to serialize and deserialize with any data format from any call site,
+/// without needing to wrap the top-level serializer or deserializer.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_stacker::Stacked;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Message {
+///     id: u64,
+///     payload: Stacked<serde_json::Value>,
+/// }
+/// ```
+///
+/// `T` must not itself contain `Stacked<T>`, as wrapping the adapters inside
+/// themselves once per level of recursion would never end. For recursive types,
+/// see [`serde_stacker::field`][crate::field].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stacked<T>(pub T);
+
+impl<T> Stacked<T> {
+    /// Unwrap the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Stacked<T> {
+    fn from(value: T) -> Self {
+        Stacked(value)
+    }
+}
+
+impl<T> Deref for Stacked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Stacked<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Serialize for Stacked<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let param = Param::top_level(Config::default());
+        crate::ser::Serialize::new(&self.0, param).serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Stacked<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let param = Param::top_level(Config::default());
+        crate::de::DeserializeSeed::new(PhantomData::<T>, param)
+            .deserialize(deserializer)
+            .map(Stacked)
+    }
+}
+
+/// Adapter for a `DeserializeSeed` which deserializes through this crate's
+/// [`Deserializer`][crate::Deserializer] adapter.
+///
+/// This makes stateful deserialization, such as into an arena or through an
+/// interner, of recursive data structures safe from stack overflow. Any seeds
+/// that the wrapped seed passes to `next_element_seed`, `next_key_seed`,
+/// `next_value_seed` and so on at nested levels are covered as well.
+///
+/// ```
+/// use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+/// use serde_stacker::StackedSeed;
+/// use std::fmt;
+///
+/// // Count the arrays in arbitrarily deeply nested arrays.
+/// struct CountArrays<'a>(&'a mut usize);
+///
+/// impl<'de, 'a> DeserializeSeed<'de> for CountArrays<'a> {
+///     type Value = ();
+///
+///     fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+///     where
+///         D: Deserializer<'de>,
+///     {
+///         deserializer.deserialize_seq(self)
+///     }
+/// }
+///
+/// impl<'de, 'a> Visitor<'de> for CountArrays<'a> {
+///     type Value = ();
+///
+///     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+///         formatter.write_str("nested arrays")
+///     }
+///
+///     fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+///     where
+///         A: SeqAccess<'de>,
+///     {
+///         *self.0 += 1;
+///         while let Some(()) = seq.next_element_seed(CountArrays(self.0))? {}
+///         Ok(())
+///     }
+/// }
+///
+/// let mut json = String::new();
+/// for _ in 0..10000 {
+///     json = format!("[{}]", json);
+/// }
+///
+/// let mut count = 0;
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// deserializer.disable_recursion_limit();
+/// StackedSeed::new(CountArrays(&mut count))
+///     .deserialize(&mut deserializer)
+///     .unwrap();
+/// assert_eq!(count, 10000);
+/// ```
+pub struct StackedSeed<S> {
+    seed: S,
+    param: Param,
+}
+
+impl<S> StackedSeed<S> {
+    /// Wrap a seed using the default [`Config`].
+    pub fn new(seed: S) -> Self {
+        Self::with_config(seed, Config::default())
+    }
+
+    /// Wrap a seed using the given configuration.
+    pub fn with_config(seed: S, config: Config) -> Self {
+        StackedSeed {
+            seed,
+            param: Param::top_level(config),
+        }
+    }
+}
+
+impl<'de, S> DeserializeSeed<'de> for StackedSeed<S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::de::DeserializeSeed::new(self.seed, self.param).deserialize(deserializer)
+    }
+}
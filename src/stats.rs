@@ -0,0 +1,107 @@
+use std::fmt::{self, Debug};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Measurements of how much nesting and stack a call through the adapters
+/// needed.
+///
+/// Attach a `Stats` to a [`Deserializer`][crate::Deserializer] or
+/// [`Serializer`][crate::Serializer] using their `with_stats` method, and read
+/// it after the call returns. A `Stats` is a handle; clones refer to the same
+/// measurements, and one which is attached to several adapters accumulates
+/// measurements across all of them.
+///
+/// ```
+/// use serde::Deserialize;
+/// use serde_json::Value;
+/// use serde_stacker::Stats;
+///
+/// # fn main() -> serde_json::Result<()> {
+/// let stats = Stats::new();
+///
+/// let mut deserializer = serde_json::Deserializer::from_str("[[], [[]]]");
+/// let deserializer = serde_stacker::Deserializer::new(&mut deserializer).with_stats(&stats);
+/// let value = Value::deserialize(deserializer)?;
+/// # let _ = value;
+///
+/// assert_eq!(stats.max_depth(), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Stats {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    max_depth: AtomicUsize,
+    segments_allocated: AtomicUsize,
+    segments_reused: AtomicUsize,
+    peak_stack_bytes: AtomicUsize,
+}
+
+impl Stats {
+    /// Stats with every measurement zero.
+    #[must_use]
+    pub fn new() -> Self {
+        Stats::default()
+    }
+
+    /// Deepest nesting of sequences, maps, and enums observed.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.inner.max_depth.load(Ordering::Relaxed)
+    }
+
+    /// Number of times a level of nesting was within the red zone and moved
+    /// onto a newly allocated stack segment.
+    #[must_use]
+    pub fn segments_allocated(&self) -> usize {
+        self.inner.segments_allocated.load(Ordering::Relaxed)
+    }
+
+    /// Number of times a level of nesting was within the red zone and moved
+    /// onto a stack segment reused from the thread's
+    /// [`segment_pool`][crate::ConfigBuilder::segment_pool], without
+    /// allocating.
+    #[must_use]
+    pub fn segments_reused(&self) -> usize {
+        self.inner.segments_reused.load(Ordering::Relaxed)
+    }
+
+    /// Largest number of bytes of stack segments allocated by the adapters
+    /// that were in use at once, not counting the thread's own stack.
+    #[must_use]
+    pub fn peak_stack_bytes(&self) -> usize {
+        self.inner.peak_stack_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_depth(&self, depth: usize) {
+        self.inner.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_segment(&self, stack_bytes: usize, reused: bool) {
+        let count = if reused {
+            &self.inner.segments_reused
+        } else {
+            &self.inner.segments_allocated
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .peak_stack_bytes
+            .fetch_max(stack_bytes, Ordering::Relaxed);
+    }
+}
+
+impl Debug for Stats {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Stats")
+            .field("max_depth", &self.max_depth())
+            .field("segments_allocated", &self.segments_allocated())
+            .field("segments_reused", &self.segments_reused())
+            .field("peak_stack_bytes", &self.peak_stack_bytes())
+            .finish()
+    }
+}
@@ -0,0 +1,269 @@
+//! Pathologically nested data for testing code that uses this crate.
+//!
+//! Each generator returns a [`Shape`], which can be serialized directly or
+//! rendered as raw JSON text using [`Shape::to_json`]. Shapes are produced on
+//! the fly during serialization rather than stored as a tree, so they can be
+//! made arbitrarily deep without themselves being a hazard to drop.
+//!
+//! [`assert_round_trip`] checks that a shape makes it through both this
+//! crate's [`Serializer`][crate::Serializer] and
+//! [`Deserializer`][crate::Deserializer] by way of a type `T` of your choice.
+//!
+//! ```
+//! use serde_stacker::json::SafeValue;
+//! use serde_stacker::testing;
+//!
+//! let shape = testing::deep_array(10000);
+//! assert!(shape.to_json().starts_with("[[[["));
+//!
+//! let _: SafeValue = testing::assert_round_trip(&shape);
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+use std::string::{String, ToString};
+
+/// A deeply nested value produced by one of the generators in this module.
+///
+/// Every shape has `depth` levels of nesting around a leaf, which is `null`
+/// for all shapes except [`alternating_enum`]. A depth of 0 is just the leaf.
+#[derive(Copy, Clone, Debug)]
+pub struct Shape {
+    kind: Kind,
+    depth: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Kind {
+    Array,
+    Object,
+    OptionChain,
+    AlternatingEnum,
+    WideAndDeep(usize),
+}
+
+/// Arrays nested `depth` deep: `[[[null]]]`.
+#[must_use]
+pub fn deep_array(depth: usize) -> Shape {
+    Shape {
+        kind: Kind::Array,
+        depth,
+    }
+}
+
+/// Objects nested `depth` deep, each with a single key `"a"`:
+/// `{"a":{"a":{"a":null}}}`.
+#[must_use]
+pub fn deep_object(depth: usize) -> Shape {
+    Shape {
+        kind: Kind::Object,
+        depth,
+    }
+}
+
+/// A linked list of `depth` nodes, serialized the way serde would serialize
+/// `Option<Box<Node>>` given `struct Node { next: Option<Box<Node>> }`:
+/// `{"next":{"next":{"next":null}}}`.
+#[must_use]
+pub fn deep_option_chain(depth: usize) -> Shape {
+    Shape {
+        kind: Kind::OptionChain,
+        depth,
+    }
+}
+
+/// Newtype variants `A` and `B` alternating `depth` deep around a unit
+/// variant `Leaf`, serialized the way serde would serialize
+/// `enum Alternating { A(Box<Alternating>), B(Box<Alternating>), Leaf }`:
+/// `{"A":{"B":{"A":"Leaf"}}}`.
+#[must_use]
+pub fn alternating_enum(depth: usize) -> Shape {
+    Shape {
+        kind: Kind::AlternatingEnum,
+        depth,
+    }
+}
+
+/// Arrays nested `depth` deep, each holding the integers from 0 up to
+/// `width - 1` followed by the next level, for a total of `width` elements:
+/// `[0,1,[0,1,[0,1,null]]]` for a width of 3.
+///
+/// # Panics
+///
+/// Panics if `width` is 0.
+#[must_use]
+pub fn wide_and_deep(width: usize, depth: usize) -> Shape {
+    assert!(width > 0, "width must be at least 1");
+    Shape {
+        kind: Kind::WideAndDeep(width),
+        depth,
+    }
+}
+
+impl Shape {
+    /// The depth this shape was generated with.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Render this shape as compact JSON text, without recursion.
+    ///
+    /// This is the same text that `serde_json` produces when serializing the
+    /// shape.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        for level in 0..self.depth {
+            match self.kind {
+                Kind::Array => json.push('['),
+                Kind::Object => json.push_str("{\"a\":"),
+                Kind::OptionChain => json.push_str("{\"next\":"),
+                Kind::AlternatingEnum => {
+                    json.push_str(if level % 2 == 0 { "{\"A\":" } else { "{\"B\":" });
+                }
+                Kind::WideAndDeep(width) => {
+                    json.push('[');
+                    for i in 0..width - 1 {
+                        json.push_str(&i.to_string());
+                        json.push(',');
+                    }
+                }
+            }
+        }
+        json.push_str(match self.kind {
+            Kind::AlternatingEnum => "\"Leaf\"",
+            _ => "null",
+        });
+        for _ in 0..self.depth {
+            json.push(match self.kind {
+                Kind::Array | Kind::WideAndDeep(_) => ']',
+                Kind::Object | Kind::OptionChain | Kind::AlternatingEnum => '}',
+            });
+        }
+        json
+    }
+
+    fn level(&self, level: usize) -> Level<'_> {
+        Level { shape: self, level }
+    }
+}
+
+/// Serializes one level of nesting per call, recursing into the next, so
+/// serializing a deep shape other than through this crate's
+/// [`Serializer`][crate::Serializer] is liable to overflow the stack.
+impl Serialize for Shape {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.level(0).serialize(serializer)
+    }
+}
+
+struct Level<'a> {
+    shape: &'a Shape,
+    level: usize,
+}
+
+impl<'a> Serialize for Level<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let kind = self.shape.kind;
+        if self.level == self.shape.depth {
+            return match kind {
+                Kind::AlternatingEnum => {
+                    serializer.serialize_unit_variant("Alternating", 2, "Leaf")
+                }
+                Kind::OptionChain => serializer.serialize_none(),
+                _ => serializer.serialize_unit(),
+            };
+        }
+        let next = self.shape.level(self.level + 1);
+        match kind {
+            Kind::Array => {
+                let mut seq = serializer.serialize_seq(Some(1))?;
+                seq.serialize_element(&next)?;
+                seq.end()
+            }
+            Kind::Object => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("a", &next)?;
+                map.end()
+            }
+            Kind::OptionChain => serializer.serialize_some(&Node(next)),
+            Kind::AlternatingEnum => {
+                let (index, variant) = if self.level % 2 == 0 {
+                    (0, "A")
+                } else {
+                    (1, "B")
+                };
+                serializer.serialize_newtype_variant("Alternating", index, variant, &next)
+            }
+            Kind::WideAndDeep(width) => {
+                let mut seq = serializer.serialize_seq(Some(width))?;
+                for i in 0..width - 1 {
+                    seq.serialize_element(&i)?;
+                }
+                seq.serialize_element(&next)?;
+                seq.end()
+            }
+        }
+    }
+}
+
+struct Node<'a>(Level<'a>);
+
+impl<'a> Serialize for Node<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut node = serializer.serialize_struct("Node", 1)?;
+        node.serialize_field("next", &self.0)?;
+        node.end()
+    }
+}
+
+/// Check that `shape` survives a round trip through this crate's adapters,
+/// and return the deserialized value.
+///
+/// This serializes the shape to JSON through [`Serializer`][crate::Serializer]
+/// and compares it against [`Shape::to_json`], deserializes a `T` from that
+/// JSON through [`Deserializer`][crate::Deserializer], then serializes the `T`
+/// again and compares that against the same JSON.
+///
+/// The `T` is returned rather than dropped, since the `Drop` impl of many
+/// types, including `serde_json::Value`, is itself recursive. Types such as
+/// [`SafeValue`][crate::json::SafeValue] or [`Value`][crate::Value] can be
+/// dropped safely at any depth.
+///
+/// # Panics
+///
+/// Panics if serialization or deserialization fails, or the JSON produced
+/// differs from the expected text.
+#[must_use]
+pub fn assert_round_trip<T>(shape: &Shape) -> T
+where
+    T: Serialize + DeserializeOwned,
+{
+    let expected = shape.to_json();
+
+    let serialized = crate::json::to_string(shape).expect("failed to serialize shape");
+    assert!(
+        serialized == expected,
+        "serializing {shape:?} produced unexpected JSON",
+    );
+
+    let value: T = crate::json::from_str(&expected).expect("failed to deserialize shape");
+
+    let reserialized = crate::json::to_string(&value).expect("failed to serialize value");
+    assert!(
+        reserialized == expected,
+        "round trip of {shape:?} produced different JSON",
+    );
+
+    value
+}
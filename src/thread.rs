@@ -0,0 +1,102 @@
+//! Running a whole serialization or deserialization on a separate thread.
+//!
+//! The [`Deserializer`][crate::Deserializer] and
+//! [`Serializer`][crate::Serializer] adapters rely on being able to switch to
+//! a newly allocated stack, which is not possible on every platform. Where it
+//! is not, as reported by [`is_growth_supported`][crate::is_growth_supported],
+//! the adapters run everything on the current stack and provide no protection
+//! beyond [`Config::max_depth`].
+//!
+//! The functions in this module instead spawn a thread whose stack is
+//! [`Config::thread_stack_size`] bytes, run the adapter on it, and wait for
+//! the result. This bounds the nesting that can be handled by the size of
+//! that one stack, but works everywhere that threads do.
+//!
+//! ```
+//! use serde::Deserialize;
+//! use serde_json::Value;
+//! use serde_stacker::Config;
+//!
+//! # fn main() -> serde_json::Result<()> {
+//! let json = "[[[[]]]]";
+//! let mut deserializer = serde_json::Deserializer::from_str(json);
+//! deserializer.disable_recursion_limit();
+//!
+//! let value: Value = if serde_stacker::is_growth_supported() {
+//!     let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+//!     Value::deserialize(deserializer)?
+//! } else {
+//!     serde_stacker::thread::deserialize(&mut deserializer, Config::default())?
+//! };
+//! # let _ = value;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use serde::de::{self, Deserialize};
+use serde::ser::{self, Serialize};
+use std::io;
+use std::panic;
+use std::string::String;
+use std::thread;
+
+/// Deserialize an instance of type `T` on a newly spawned thread, through
+/// this crate's [`Deserializer`][crate::Deserializer] with the given
+/// configuration.
+///
+/// A panic during deserialization is propagated to the caller.
+///
+/// # Errors
+///
+/// Returns any error from deserialization, or a custom error if the thread
+/// could not be spawned.
+pub fn deserialize<'de, T, D>(deserializer: D, config: Config) -> Result<T, D::Error>
+where
+    T: Deserialize<'de> + Send,
+    D: de::Deserializer<'de> + Send,
+    D::Error: Send,
+{
+    run(config, move || {
+        T::deserialize(crate::Deserializer::with_config(deserializer, config))
+    })
+    .unwrap_or_else(|error| Err(de::Error::custom(error)))
+}
+
+/// Serialize the given value on a newly spawned thread, through this crate's
+/// [`Serializer`][crate::Serializer] with the given configuration.
+///
+/// A panic during serialization is propagated to the caller.
+///
+/// # Errors
+///
+/// Returns any error from serialization, or a custom error if the thread
+/// could not be spawned.
+pub fn serialize<T, S>(value: &T, serializer: S, config: Config) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized + Serialize + Sync,
+    S: ser::Serializer + Send,
+    S::Ok: Send,
+    S::Error: Send,
+{
+    run(config, move || {
+        value.serialize(crate::Serializer::with_config(serializer, config))
+    })
+    .unwrap_or_else(|error| Err(ser::Error::custom(error)))
+}
+
+fn run<R>(config: Config, f: impl FnOnce() -> R + Send) -> io::Result<R>
+where
+    R: Send,
+{
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .name(String::from("serde_stacker"))
+            .stack_size(config.thread_stack_size())
+            .spawn_scoped(scope, f)?;
+        match handle.join() {
+            Ok(ret) => Ok(ret),
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    })
+}
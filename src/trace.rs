@@ -0,0 +1,79 @@
+// Structured events emitted through `tracing` when the "tracing" feature is
+// enabled. Every event uses the target "serde_stacker".
+
+use crate::param::LimitExceeded;
+use crate::stats::Stats;
+use std::sync::Arc;
+use tracing::Level;
+
+// Measurements of one top-level call through the adapters, shared by every
+// Param derived from it. Dropping the last of them means the call is done, at
+// which point the summary event is emitted.
+pub struct Summary {
+    operation: &'static str,
+    pub stats: Stats,
+}
+
+impl Summary {
+    pub fn new(operation: &'static str) -> Option<Arc<Self>> {
+        if tracing::enabled!(target: "serde_stacker", Level::DEBUG) {
+            Some(Arc::new(Summary {
+                operation,
+                stats: Stats::new(),
+            }))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Summary {
+    fn drop(&mut self) {
+        tracing::debug!(
+            target: "serde_stacker",
+            operation = self.operation,
+            max_depth = self.stats.max_depth(),
+            segments_allocated = self.stats.segments_allocated(),
+            segments_reused = self.stats.segments_reused(),
+            peak_stack_bytes = self.stats.peak_stack_bytes(),
+            "finished",
+        );
+    }
+}
+
+pub fn segment_entered(depth: usize, segments: usize, stack_size: usize, reused: bool) {
+    if reused {
+        tracing::debug!(
+            target: "serde_stacker",
+            depth,
+            segments,
+            stack_size,
+            "reusing pooled stack segment",
+        );
+    } else {
+        tracing::debug!(
+            target: "serde_stacker",
+            depth,
+            segments,
+            stack_size,
+            "allocating stack segment",
+        );
+    }
+}
+
+pub fn limit_exceeded(depth: usize, error: &LimitExceeded) {
+    match *error {
+        LimitExceeded::Depth(max_depth) => tracing::warn!(
+            target: "serde_stacker",
+            depth,
+            max_depth,
+            "exceeded maximum nesting depth",
+        ),
+        LimitExceeded::StackBudget(stack_budget) => tracing::warn!(
+            target: "serde_stacker",
+            depth,
+            stack_budget,
+            "exceeded stack budget",
+        ),
+    }
+}
@@ -0,0 +1,328 @@
+use crate::config::Config;
+use core::cell::RefCell;
+use core::fmt;
+use serde::de;
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq};
+
+/// Transcode from any self-describing deserializer into any serializer,
+/// growing the stack as needed on both sides.
+///
+/// The deserializer and serializer are wrapped in this crate's
+/// [`Deserializer`][crate::Deserializer] and [`Serializer`][crate::Serializer]
+/// adapters, and every value produced by the one is passed on to the other
+/// without materializing it in memory. Errors from the deserializer are
+/// converted to the serializer's error type using `ser::Error::custom`.
+///
+/// # Errors
+///
+/// Returns any error from the serializer, any error from the deserializer, or
+/// an error if either side exceeds the default configuration's limits.
+///
+/// ```
+/// let mut json = String::new();
+/// for _ in 0..10000 {
+///     json = format!("[{}]", json);
+/// }
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// deserializer.disable_recursion_limit();
+/// let mut out = Vec::new();
+/// let mut serializer = serde_json::Serializer::new(&mut out);
+/// serde_stacker::transcode(&mut deserializer, &mut serializer).unwrap();
+/// assert_eq!(out, json.as_bytes());
+/// ```
+pub fn transcode<'de, D, S>(deserializer: D, serializer: S) -> Result<S::Ok, S::Error>
+where
+    D: de::Deserializer<'de>,
+    S: ser::Serializer,
+{
+    transcode_with_config(deserializer, serializer, Config::default())
+}
+
+/// Transcode from any self-describing deserializer into any serializer, using
+/// the given configuration for both sides.
+///
+/// # Errors
+///
+/// Returns any error from the serializer, any error from the deserializer, or
+/// an error if either side exceeds the configured `max_depth` or
+/// `stack_budget`.
+pub fn transcode_with_config<'de, D, S>(
+    deserializer: D,
+    serializer: S,
+    config: Config,
+) -> Result<S::Ok, S::Error>
+where
+    D: de::Deserializer<'de>,
+    S: ser::Serializer,
+{
+    let deserializer = crate::Deserializer::with_config(deserializer, config);
+    let serializer = crate::Serializer::with_config(serializer, config);
+    Transcoder::new(deserializer).serialize(serializer)
+}
+
+// Serializes the value produced by a deserializer. Nested values are
+// transcoded by handing a new Transcoder to the serializer for each element,
+// so both the deserializer and serializer recurse once per level, through the
+// adapters that protect each of them.
+struct Transcoder<D>(RefCell<Option<D>>);
+
+impl<D> Transcoder<D> {
+    fn new(deserializer: D) -> Self {
+        Transcoder(RefCell::new(Some(deserializer)))
+    }
+}
+
+impl<'de, D> Serialize for Transcoder<D>
+where
+    D: de::Deserializer<'de>,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.0
+            .borrow_mut()
+            .take()
+            .expect("Transcoder may only be serialized once")
+            .deserialize_any(Visitor(serializer))
+            .map_err(ser::Error::custom)
+    }
+}
+
+struct Visitor<S>(S);
+
+impl<'de, S> de::Visitor<'de> for Visitor<S>
+where
+    S: ser::Serializer,
+{
+    type Value = S::Ok;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_bool(v).map_err(de::Error::custom)
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_i8(v).map_err(de::Error::custom)
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_i16(v).map_err(de::Error::custom)
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_i32(v).map_err(de::Error::custom)
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_i64(v).map_err(de::Error::custom)
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_i128(v).map_err(de::Error::custom)
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_u8(v).map_err(de::Error::custom)
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_u16(v).map_err(de::Error::custom)
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_u32(v).map_err(de::Error::custom)
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_u64(v).map_err(de::Error::custom)
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_u128(v).map_err(de::Error::custom)
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_f32(v).map_err(de::Error::custom)
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_f64(v).map_err(de::Error::custom)
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_char(v).map_err(de::Error::custom)
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_str(v).map_err(de::Error::custom)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_bytes(v).map_err(de::Error::custom)
+    }
+
+    fn visit_none<E>(self) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_none().map_err(de::Error::custom)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<S::Ok, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0
+            .serialize_some(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_unit<E>(self) -> Result<S::Ok, E>
+    where
+        E: de::Error,
+    {
+        self.0.serialize_unit().map_err(de::Error::custom)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<S::Ok, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0
+            .serialize_newtype_struct("<unknown>", &Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<S::Ok, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut s = self
+            .0
+            .serialize_seq(seq.size_hint())
+            .map_err(de::Error::custom)?;
+        while let Some(()) = seq.next_element_seed(SeqSeed(&mut s))? {}
+        s.end().map_err(de::Error::custom)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<S::Ok, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut s = self
+            .0
+            .serialize_map(map.size_hint())
+            .map_err(de::Error::custom)?;
+        while let Some(()) = map.next_key_seed(KeySeed(&mut s))? {
+            map.next_value_seed(ValueSeed(&mut s))?;
+        }
+        s.end().map_err(de::Error::custom)
+    }
+}
+
+struct SeqSeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S> de::DeserializeSeed<'de> for SeqSeed<'a, S>
+where
+    S: SerializeSeq,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0
+            .serialize_element(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
+
+struct KeySeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S> de::DeserializeSeed<'de> for KeySeed<'a, S>
+where
+    S: SerializeMap,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0
+            .serialize_key(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
+
+struct ValueSeed<'a, S>(&'a mut S);
+
+impl<'de, 'a, S> de::DeserializeSeed<'de> for ValueSeed<'a, S>
+where
+    S: SerializeMap,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        self.0
+            .serialize_value(&Transcoder::new(deserializer))
+            .map_err(de::Error::custom)
+    }
+}
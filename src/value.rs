@@ -0,0 +1,547 @@
+use crate::config::Config;
+use crate::param::Param;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Write as _};
+use core::mem;
+use core::slice;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+/// Dynamically typed value which is safe to work with at any depth.
+///
+/// `Value` can represent the data of any self-describing format, the way
+/// `serde_json::Value` does for JSON. Unlike such types, its `Drop`, `Clone`,
+/// `PartialEq` and `Debug` impls are not recursive: they walk the tree using a
+/// work stack on the heap, so they use the same small amount of stack no
+/// matter how deeply the value is nested.
+///
+/// Deserialization is not heap-driven in the same way, and cannot be for a
+/// generic `Deserializer`. Serde hands each nested sequence or map to the
+/// visitor as a `SeqAccess` or `MapAccess` which borrows from the
+/// deserializer's own stack frame, and that frame cannot return until the
+/// nested value has been consumed in full, so the data format holds at least
+/// one frame of its own per level of nesting however the visitor is written.
+/// A work stack on the heap would only replace the visitor's part of each
+/// level, which is already small for `Value`. Instead, the `Deserialize` and
+/// `Serialize` impls for `Value` run through this crate's
+/// [`Deserializer`][crate::Deserializer] and
+/// [`Serializer`][crate::Serializer] adapters with the default [`Config`],
+/// the same as [`Stacked`][crate::Stacked] does, which makes them safe to use
+/// with any data format from any call site. Stack use is then proportional to
+/// depth times the data format's frame size, in segments allocated on demand.
+///
+/// ```
+/// use serde_stacker::Value;
+///
+/// let mut json = String::new();
+/// for _ in 0..100000 {
+///     json = format!("[{}]", json);
+/// }
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// deserializer.disable_recursion_limit();
+/// let value: Value = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+///
+/// let copy = value.clone();
+/// assert_eq!(value, copy);
+/// assert!(format!("{:?}", value).starts_with("Seq([Seq([Seq(["));
+/// ```
+#[derive(Default)]
+pub enum Value {
+    /// Unit, or the absence of an optional value.
+    #[default]
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Seq(Vec<Value>),
+    /// Entries of a map, in the order they appeared in the input. Keys may be
+    /// of any type.
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    // Moves the children of a Seq or Map onto the given stack, leaving it
+    // empty.
+    fn take_children(&mut self, stack: &mut Vec<Value>) {
+        match self {
+            Value::Seq(seq) => stack.append(seq),
+            Value::Map(map) => {
+                for (k, v) in mem::take(map) {
+                    stack.push(k);
+                    stack.push(v);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut value) = stack.pop() {
+            value.take_children(&mut stack);
+        }
+    }
+}
+
+impl Clone for Value {
+    fn clone(&self) -> Self {
+        // Containers under construction, each with its flattened elements so
+        // far. For maps, keys and values alternate.
+        let mut stack: Vec<(bool, Vec<Value>)> = Vec::new();
+        for event in Walk::new(self) {
+            let value = match event {
+                Event::Seq(len) => {
+                    stack.push((false, Vec::with_capacity(len)));
+                    continue;
+                }
+                Event::Map(len) => {
+                    stack.push((true, Vec::with_capacity(len * 2)));
+                    continue;
+                }
+                Event::End => {
+                    let (is_map, elements) = stack.pop().unwrap();
+                    if is_map {
+                        let mut entries = Vec::with_capacity(elements.len() / 2);
+                        let mut elements = elements.into_iter();
+                        while let (Some(k), Some(v)) = (elements.next(), elements.next()) {
+                            entries.push((k, v));
+                        }
+                        Value::Map(entries)
+                    } else {
+                        Value::Seq(elements)
+                    }
+                }
+                Event::Leaf(leaf) => match leaf {
+                    Value::Null => Value::Null,
+                    Value::Bool(b) => Value::Bool(*b),
+                    Value::I64(n) => Value::I64(*n),
+                    Value::U64(n) => Value::U64(*n),
+                    Value::F64(n) => Value::F64(*n),
+                    Value::String(s) => Value::String(s.clone()),
+                    Value::Bytes(b) => Value::Bytes(b.clone()),
+                    Value::Seq(_) | Value::Map(_) => unreachable!(),
+                },
+            };
+            match stack.last_mut() {
+                Some((_, elements)) => elements.push(value),
+                None => return value,
+            }
+        }
+        unreachable!()
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        let mut a = Walk::new(self);
+        let mut b = Walk::new(other);
+        loop {
+            let equal = match (a.next(), b.next()) {
+                (None, None) => return true,
+                (Some(Event::Seq(a)), Some(Event::Seq(b)))
+                | (Some(Event::Map(a)), Some(Event::Map(b))) => a == b,
+                (Some(Event::End), Some(Event::End)) => true,
+                (Some(Event::Leaf(a)), Some(Event::Leaf(b))) => match (a, b) {
+                    (Value::Null, Value::Null) => true,
+                    (Value::Bool(a), Value::Bool(b)) => a == b,
+                    (Value::I64(a), Value::I64(b)) => a == b,
+                    (Value::U64(a), Value::U64(b)) => a == b,
+                    (Value::F64(a), Value::F64(b)) => a == b,
+                    (Value::String(a), Value::String(b)) => a == b,
+                    (Value::Bytes(a), Value::Bytes(b)) => a == b,
+                    _ => false,
+                },
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
+        }
+    }
+}
+
+// Formatted the same as a derived Debug impl would, except that the entries of
+// a Map are written in `{k: v}` style like those of a standard map type.
+// Includes the alternate multi-line form, and does not recurse.
+impl Debug for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        struct Frame {
+            is_map: bool,
+            len: usize,
+            // Number of keys and values written so far.
+            written: usize,
+        }
+
+        let pretty = formatter.alternate();
+        let mut stack: Vec<Frame> = Vec::new();
+        for event in Walk::new(self) {
+            // In the alternate form, each Seq or Map is a tuple variant
+            // containing a list, which adds two levels of indentation.
+            let level = stack.len() * 2;
+            if let Some(frame) = stack.last() {
+                if !matches!(event, Event::End) {
+                    if frame.is_map && frame.written % 2 == 1 {
+                        formatter.write_str(": ")?;
+                    } else if pretty {
+                        formatter.write_char('\n')?;
+                        indent(formatter, level)?;
+                    } else if frame.written > 0 {
+                        formatter.write_str(", ")?;
+                    }
+                }
+            }
+            let open = match event {
+                Event::Seq(len) => Some((false, len, "Seq", '[')),
+                Event::Map(len) => Some((true, len * 2, "Map", '{')),
+                Event::End | Event::Leaf(_) => None,
+            };
+            if let Some((is_map, len, variant, bracket)) = open {
+                formatter.write_str(variant)?;
+                formatter.write_char('(')?;
+                if pretty {
+                    formatter.write_char('\n')?;
+                    indent(formatter, level + 1)?;
+                }
+                formatter.write_char(bracket)?;
+                stack.push(Frame {
+                    is_map,
+                    len,
+                    written: 0,
+                });
+                continue;
+            }
+            match event {
+                Event::End => {
+                    let frame = stack.pop().unwrap();
+                    let level = stack.len() * 2;
+                    if pretty && frame.len > 0 {
+                        formatter.write_char('\n')?;
+                        indent(formatter, level + 1)?;
+                    }
+                    formatter.write_char(if frame.is_map { '}' } else { ']' })?;
+                    if pretty {
+                        formatter.write_str(",\n")?;
+                        indent(formatter, level)?;
+                    }
+                    formatter.write_char(')')?;
+                }
+                Event::Leaf(leaf) if pretty => {
+                    let mut indented = Indented {
+                        formatter: &mut *formatter,
+                        level,
+                    };
+                    write!(indented, "{:#?}", Leaf(leaf))?;
+                }
+                Event::Leaf(leaf) => Debug::fmt(&Leaf(leaf), formatter)?,
+                Event::Seq(_) | Event::Map(_) => unreachable!(),
+            }
+            if let Some(frame) = stack.last_mut() {
+                frame.written += 1;
+                if pretty && !(frame.is_map && frame.written % 2 == 1) {
+                    formatter.write_char(',')?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Debug representation of a value other than Seq or Map.
+struct Leaf<'a>(&'a Value);
+
+impl<'a> Debug for Leaf<'a> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Value::Null => formatter.write_str("Null"),
+            Value::Bool(b) => formatter.debug_tuple("Bool").field(b).finish(),
+            Value::I64(n) => formatter.debug_tuple("I64").field(n).finish(),
+            Value::U64(n) => formatter.debug_tuple("U64").field(n).finish(),
+            Value::F64(n) => formatter.debug_tuple("F64").field(n).finish(),
+            Value::String(s) => formatter.debug_tuple("String").field(s).finish(),
+            Value::Bytes(b) => formatter.debug_tuple("Bytes").field(b).finish(),
+            Value::Seq(_) | Value::Map(_) => unreachable!(),
+        }
+    }
+}
+
+// Indents every line but the first by the given number of levels.
+struct Indented<'a, 'b> {
+    formatter: &'a mut fmt::Formatter<'b>,
+    level: usize,
+}
+
+impl<'a, 'b> fmt::Write for Indented<'a, 'b> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.formatter.write_str(first)?;
+        }
+        for line in lines {
+            self.formatter.write_char('\n')?;
+            indent(self.formatter, self.level)?;
+            self.formatter.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+fn indent(formatter: &mut fmt::Formatter, level: usize) -> fmt::Result {
+    for _ in 0..level {
+        formatter.write_str("    ")?;
+    }
+    Ok(())
+}
+
+// Pre-order traversal of a value, which produces the same sequence of events
+// as the value's Serialize impl would, using a stack on the heap.
+struct Walk<'a> {
+    stack: Vec<Children<'a>>,
+    next: Option<&'a Value>,
+}
+
+enum Event<'a> {
+    // Any value other than Seq or Map.
+    Leaf(&'a Value),
+    // Start of a Seq with the given number of elements, which follow.
+    Seq(usize),
+    // Start of a Map with the given number of entries, whose keys and values
+    // follow in alternation.
+    Map(usize),
+    // End of the innermost Seq or Map.
+    End,
+}
+
+enum Children<'a> {
+    Seq(slice::Iter<'a, Value>),
+    Map(slice::Iter<'a, (Value, Value)>, Option<&'a Value>),
+}
+
+impl<'a> Walk<'a> {
+    fn new(value: &'a Value) -> Self {
+        Walk {
+            stack: Vec::new(),
+            next: Some(value),
+        }
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = if let Some(value) = self.next.take() {
+            value
+        } else {
+            let child = match self.stack.last_mut()? {
+                Children::Seq(iter) => iter.next(),
+                Children::Map(iter, pending) => match pending.take() {
+                    Some(v) => Some(v),
+                    None => iter.next().map(|(k, v)| {
+                        *pending = Some(v);
+                        k
+                    }),
+                },
+            };
+            if let Some(child) = child {
+                child
+            } else {
+                self.stack.pop();
+                return Some(Event::End);
+            }
+        };
+        Some(match value {
+            Value::Seq(seq) => {
+                self.stack.push(Children::Seq(seq.iter()));
+                Event::Seq(seq.len())
+            }
+            Value::Map(map) => {
+                self.stack.push(Children::Map(map.iter(), None));
+                Event::Map(map.len())
+            }
+            leaf => Event::Leaf(leaf),
+        })
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let param = Param::top_level(Config::default());
+        crate::ser::Serialize::new(&Nested(self), param).serialize(serializer)
+    }
+}
+
+// Serializes a value which is already inside of the adapter, so that nested
+// values do not each start over at the top level.
+struct Nested<'a>(&'a Value);
+
+impl<'a> Serialize for Nested<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::I64(n) => serializer.serialize_i64(*n),
+            Value::U64(n) => serializer.serialize_u64(*n),
+            Value::F64(n) => serializer.serialize_f64(*n),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::Seq(seq) => {
+                let mut serializer = serializer.serialize_seq(Some(seq.len()))?;
+                for element in seq {
+                    serializer.serialize_element(&Nested(element))?;
+                }
+                serializer.end()
+            }
+            Value::Map(map) => {
+                let mut serializer = serializer.serialize_map(Some(map.len()))?;
+                for (k, v) in map {
+                    serializer.serialize_entry(&Nested(k), &Nested(v))?;
+                }
+                serializer.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let param = Param::top_level(Config::default());
+        crate::de::DeserializeSeed::new(ValueSeed, param).deserialize(deserializer)
+    }
+}
+
+// Deserializes a value which is already inside of the adapter.
+struct ValueSeed;
+
+impl<'de> DeserializeSeed<'de> for ValueSeed {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::I64(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        match i64::try_from(v) {
+            Ok(v) => Ok(Value::I64(v)),
+            Err(_) => Err(E::custom(format_args!("i128 out of range: {v}"))),
+        }
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::U64(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        match u64::try_from(v) {
+            Ok(v) => Ok(Value::U64(v)),
+            Err(_) => Err(E::custom(format_args!("u128 out of range: {v}"))),
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::F64(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(String::from(v)))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ValueSeed.deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ValueSeed.deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut vec = Vec::new();
+        while let Some(element) = seq.next_element_seed(ValueSeed)? {
+            vec.push(element);
+        }
+        Ok(Value::Seq(vec))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry_seed(ValueSeed, ValueSeed)? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
@@ -1,7 +1,17 @@
+#![cfg(feature = "std")]
 #![allow(clippy::uninlined_format_args)]
 
+use serde::de::value::{self, MapDeserializer, SeqDeserializer};
+use serde::de::{DeserializeSeed, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use serde_stacker::{Config, Growth, GrowthObserver, Stacked, StackedSeed, Stats};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 #[test]
 fn test_deserialize() {
@@ -36,6 +46,818 @@ fn test_serialize() {
     assert_eq!(out.len(), 10000 + 4 + 10000);
 }
 
+#[test]
+fn test_serialize_nested_fields() {
+    // Nesting through serialize_some and serialize_field rather than a
+    // sequence.
+    #[derive(Serialize)]
+    struct Node {
+        next: Option<Box<Node>>,
+    }
+
+    let mut node = Node { next: None };
+    for _ in 0..10000 {
+        node = Node {
+            next: Some(Box::new(node)),
+        };
+    }
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let serializer = serde_stacker::Serializer::new(&mut serializer);
+    let result = node.serialize(serializer);
+
+    let mut next = node.next.take();
+    while let Some(mut node) = next {
+        next = node.next.take();
+    }
+
+    result.unwrap();
+    let len = "{\"next\":".len() * 10001 + "null".len() + "}".len() * 10001;
+    assert_eq!(out.len(), len);
+}
+
+#[test]
+fn test_max_depth() {
+    let json = "[[[[]]]]";
+    let value: Value = serde_json::from_str(json).unwrap();
+
+    let config = Config::builder().max_depth(4).build().unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    Value::deserialize(deserializer).unwrap();
+
+    let mut serializer = serde_json::Serializer::new(Vec::new());
+    let serializer = serde_stacker::Serializer::with_config(&mut serializer, config);
+    value.serialize(serializer).unwrap();
+
+    let config = Config::builder().max_depth(3).build().unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Value::deserialize(deserializer).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 3"));
+
+    let mut serializer = serde_json::Serializer::new(Vec::new());
+    let serializer = serde_stacker::Serializer::with_config(&mut serializer, config);
+    let error = value.serialize(serializer).unwrap_err();
+    assert_eq!(error.to_string(), "exceeded maximum nesting depth of 3");
+}
+
+#[test]
+fn test_stack_budget() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder()
+        .stack_size(1024 * 1024)
+        .stack_budget(1024 * 1024)
+        .build()
+        .unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Value::deserialize(deserializer).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded stack budget of 1048576 bytes"));
+}
+
+#[test]
+fn test_stack_budget_nested() {
+    // Deserializes a string containing JSON through a separate adapter with
+    // no budget of its own.
+    struct Embedded;
+
+    impl<'de> Deserialize<'de> for Embedded {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let json = String::deserialize(deserializer)?;
+            let mut deserializer = serde_json::Deserializer::from_str(&json);
+            deserializer.disable_recursion_limit();
+            let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+            match Value::deserialize(deserializer) {
+                Ok(value) => {
+                    drop_carefully(value);
+                    Ok(Embedded)
+                }
+                Err(error) => Err(serde::de::Error::custom(error)),
+            }
+        }
+    }
+
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+    let json = serde_json::to_string(&[json]).unwrap();
+
+    let config = Config::builder()
+        .stack_size(1024 * 1024)
+        .stack_budget(1024 * 1024)
+        .build()
+        .unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Vec::<Embedded>::deserialize(deserializer).err().unwrap();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded stack budget of 1048576 bytes"));
+}
+
+#[test]
+fn test_segment_pool() {
+    let mut nested = String::new();
+    for _ in 0..2000 {
+        nested = format!("[{}]", nested);
+    }
+    let json = format!("[{},{},{}]", nested, nested, nested);
+
+    let config = Config::builder()
+        .red_zone(64 * 1024)
+        .stack_size(128 * 1024)
+        .segment_pool(4)
+        .build()
+        .unwrap();
+
+    let stats = Stats::new();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer =
+        serde_stacker::Deserializer::with_config(&mut deserializer, config).with_stats(&stats);
+    let value = Value::deserialize(deserializer).unwrap();
+    assert!(stats.segments_reused() > 0);
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let serializer = serde_stacker::Serializer::with_config(&mut serializer, config);
+    let result = value.serialize(serializer);
+
+    drop_carefully(value);
+
+    result.unwrap();
+    assert_eq!(out, json.as_bytes());
+}
+
+#[test]
+fn test_segment_pool_stacker() {
+    // Runs on each new segment, where stacker must not believe there is more
+    // stack left than the segment has.
+    struct Check;
+
+    impl GrowthObserver for Check {
+        fn segment_entered(&self, growth: &Growth) {
+            let remaining = stacker::remaining_stack().unwrap_or(0);
+            assert!(remaining <= growth.stack_size());
+        }
+
+        fn segment_exited(&self, _growth: &Growth) {}
+    }
+
+    let mut nested = String::new();
+    for _ in 0..2000 {
+        nested = format!("[{}]", nested);
+    }
+    let json = format!("[{},{},{}]", nested, nested, nested);
+
+    let config = Config::builder()
+        .red_zone(64 * 1024)
+        .stack_size(128 * 1024)
+        .segment_pool(4)
+        .build()
+        .unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config)
+        .with_observer(Arc::new(Check));
+    let value = Value::deserialize(deserializer).unwrap();
+
+    drop_carefully(value);
+}
+
+#[test]
+fn test_stats() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder().stack_size(1024 * 1024).build().unwrap();
+
+    let stats = Stats::new();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer =
+        serde_stacker::Deserializer::with_config(&mut deserializer, config).with_stats(&stats);
+    let value = Value::deserialize(deserializer).unwrap();
+    assert_eq!(stats.max_depth(), 10000);
+    assert!(stats.segments_allocated() > 1);
+    assert!(stats.peak_stack_bytes() >= 2 * 1024 * 1024);
+    assert_eq!(stats.peak_stack_bytes() % (1024 * 1024), 0);
+
+    let stats = Stats::new();
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let serializer =
+        serde_stacker::Serializer::with_config(&mut serializer, config).with_stats(&stats);
+    let result = value.serialize(serializer);
+
+    drop_carefully(value);
+
+    result.unwrap();
+    assert_eq!(stats.max_depth(), 10000);
+    assert!(stats.segments_allocated() > 1);
+
+    let stats = Stats::new();
+    let mut deserializer = serde_json::Deserializer::from_str("[1, [2]]");
+    let deserializer = serde_stacker::Deserializer::new(&mut deserializer).with_stats(&stats);
+    Value::deserialize(deserializer).unwrap();
+    assert_eq!(stats.max_depth(), 2);
+    assert_eq!(stats.peak_stack_bytes(), 0);
+}
+
+#[test]
+fn test_observer() {
+    #[derive(Default)]
+    struct Counter {
+        entered: AtomicUsize,
+        exited: AtomicUsize,
+        in_use: AtomicUsize,
+    }
+
+    impl GrowthObserver for Counter {
+        fn segment_entered(&self, growth: &Growth) {
+            assert!(growth.depth() > 0);
+            assert_eq!(growth.stack_size(), 1024 * 1024);
+            self.entered.fetch_add(1, Ordering::Relaxed);
+            let in_use = self.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+            assert_eq!(growth.segments(), in_use);
+        }
+
+        fn segment_exited(&self, growth: &Growth) {
+            let in_use = self.in_use.fetch_sub(1, Ordering::Relaxed);
+            assert_eq!(growth.segments(), in_use);
+            self.exited.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder().stack_size(1024 * 1024).build().unwrap();
+
+    let counter = Arc::new(Counter::default());
+    let stats = Stats::new();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config)
+        .with_stats(&stats)
+        .with_observer(counter.clone());
+    let value = Value::deserialize(deserializer).unwrap();
+    assert!(counter.entered.load(Ordering::Relaxed) > 1);
+    assert_eq!(
+        counter.entered.load(Ordering::Relaxed),
+        stats.segments_allocated(),
+    );
+    assert_eq!(
+        counter.exited.load(Ordering::Relaxed),
+        stats.segments_allocated()
+    );
+
+    let counter = Arc::new(Counter::default());
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let serializer = serde_stacker::Serializer::with_config(&mut serializer, config)
+        .with_observer(counter.clone());
+    let result = value.serialize(serializer);
+
+    drop_carefully(value);
+
+    result.unwrap();
+    assert!(counter.entered.load(Ordering::Relaxed) > 1);
+    assert_eq!(
+        counter.entered.load(Ordering::Relaxed),
+        counter.exited.load(Ordering::Relaxed),
+    );
+}
+
+#[test]
+fn test_annotate_errors() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Point {
+        x: u8,
+        y: u8,
+    }
+
+    let json = r#"[[], [[1, "x"]]]"#;
+    let config = Config::builder().annotate_errors(true).build().unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Vec::<Vec<Vec<u8>>>::deserialize(deserializer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: string \"x\", expected u8 (at nesting depth 3) at line 1 column 14",
+    );
+
+    // Produced by the visitor rather than by serde_json.
+    let mut deserializer = serde_json::Deserializer::from_str(r#"[[{"x": 1}]]"#);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Vec::<Vec<Point>>::deserialize(deserializer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "missing field `y` (at nesting depth 2) at line 1 column 10",
+    );
+
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+    let error = Vec::<Vec<Vec<u8>>>::deserialize(deserializer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: string \"x\", expected u8 at line 1 column 13",
+    );
+}
+
+#[test]
+fn test_track_path() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Outer {
+        a: Vec<Inner>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum Inner {
+        Unit,
+        Map(BTreeMap<String, Vec<u8>>),
+    }
+
+    let json = r#"{"a": ["Unit", {"Map": {"ok": [], "not ok": [1, "x"]}}]}"#;
+
+    let config = Config::builder().track_path(true).build().unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Outer::deserialize(deserializer).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: string \"x\", expected u8 (at path $.a[1].Map[\"not ok\"][1]) at line 1 column 52",
+    );
+
+    let config = Config::builder()
+        .track_path(true)
+        .annotate_errors(true)
+        .build()
+        .unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Outer::deserialize(deserializer).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("(at path $.a[1].Map[\"not ok\"][1], nesting depth 5)"));
+
+    let mut json = String::from("[1 2]");
+    for _ in 0..9999 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder().track_path(true).build().unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+    let error = Value::deserialize(deserializer).unwrap_err();
+    let expected = format!("(at path ${}[1])", "[0]".repeat(9999));
+    assert!(error.to_string().contains(&expected));
+}
+
+#[test]
+fn test_track_path_recovery() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Inner {
+        a: u8,
+        b: String,
+    }
+
+    // Deserializes a sequence in which every element fails, and collects the
+    // errors.
+    struct Errors(Vec<String>);
+
+    impl<'de> Deserialize<'de> for Errors {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(Errors(Vec::new()))
+        }
+    }
+
+    impl<'de> Visitor<'de> for Errors {
+        type Value = Errors;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a sequence")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Errors, A::Error> {
+            loop {
+                match seq.next_element::<Inner>() {
+                    Ok(Some(_)) => panic!("expected an error"),
+                    Ok(None) => return Ok(self),
+                    Err(error) => self.0.push(error.to_string()),
+                }
+            }
+        }
+    }
+
+    // Unlike a parser, these can keep going after an element fails.
+    let maps = vec![
+        MapDeserializer::new(vec![("a", 1), ("a", 2)].into_iter()),
+        MapDeserializer::new(vec![("a", 1), ("b", 2)].into_iter()),
+    ];
+    let deserializer = SeqDeserializer::<_, value::Error>::new(maps.into_iter());
+
+    let config = Config::builder().track_path(true).build().unwrap();
+    let deserializer = serde_stacker::Deserializer::with_config(deserializer, config);
+    let errors = Errors::deserialize(deserializer).unwrap();
+    assert_eq!(
+        errors.0,
+        [
+            "duplicate field `a` (at path $[0])",
+            "invalid type: integer `2`, expected a string (at path $[1].b)",
+        ],
+    );
+}
+
+#[test]
+fn test_annotate_errors_recovery() {
+    // Deserializes a sequence of u8 in which every element fails, and collects
+    // the errors.
+    struct Errors(Vec<String>);
+
+    impl<'de> Deserialize<'de> for Errors {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(Errors(Vec::new()))
+        }
+    }
+
+    impl<'de> Visitor<'de> for Errors {
+        type Value = Errors;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a sequence")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Errors, A::Error> {
+            loop {
+                match seq.next_element::<u8>() {
+                    Ok(Some(_)) => panic!("expected an error"),
+                    Ok(None) => return Ok(self),
+                    Err(error) => self.0.push(error.to_string()),
+                }
+            }
+        }
+    }
+
+    let deserializer = SeqDeserializer::<_, value::Error>::new(["x", "y", "z"].into_iter());
+
+    let config = Config::builder()
+        .track_path(true)
+        .annotate_errors(true)
+        .build()
+        .unwrap();
+    let deserializer = serde_stacker::Deserializer::with_config(deserializer, config);
+    let errors = Errors::deserialize(deserializer).unwrap();
+    assert_eq!(
+        errors.0,
+        [
+            "invalid type: string \"x\", expected u8 (at path $[0], nesting depth 1)",
+            "invalid type: string \"y\", expected u8 (at path $[1], nesting depth 1)",
+            "invalid type: string \"z\", expected u8 (at path $[2], nesting depth 1)",
+        ],
+    );
+}
+
+#[test]
+fn test_thread() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder()
+        .thread_stack_size(128 * 1024 * 1024)
+        .build()
+        .unwrap();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let value: Value = serde_stacker::thread::deserialize(&mut deserializer, config).unwrap();
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let result = serde_stacker::thread::serialize(&value, &mut serializer, config);
+
+    drop_carefully(value);
+
+    result.unwrap();
+    assert_eq!(out, json.as_bytes());
+
+    let config = Config::builder().max_depth(3).build().unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str("[[[[]]]]");
+    let error =
+        serde_stacker::thread::deserialize::<Value, _>(&mut deserializer, config).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 3"));
+}
+
+#[test]
+fn test_is_growth_supported() {
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        assert!(serde_stacker::is_growth_supported());
+    }
+}
+
+#[test]
+fn test_transcode() {
+    let mut json = String::from("null");
+    for _ in 0..10000 {
+        json = format!("[{{\"k\":{}}}, 1.5, null, \"s\", true]", json);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    serde_stacker::transcode(&mut deserializer, &mut serializer).unwrap();
+    assert_eq!(out, json.replace(", ", ",").as_bytes());
+
+    let config = Config::builder().max_depth(3).build().unwrap();
+    let mut deserializer = serde_json::Deserializer::from_str("[[[[]]]]");
+    let mut serializer = serde_json::Serializer::new(Vec::new());
+    let error = serde_stacker::transcode_with_config(&mut deserializer, &mut serializer, config)
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 3"));
+}
+
+#[test]
+fn test_debug() {
+    #[derive(Serialize, Debug)]
+    struct Unit;
+
+    #[derive(Serialize, Debug)]
+    struct Newtype(u8);
+
+    #[derive(Serialize, Debug)]
+    struct Tuple(i32, &'static str);
+
+    #[derive(Serialize, Debug)]
+    enum Enum {
+        Unit,
+        Newtype(Option<char>),
+        Tuple(f64, bool),
+        Struct { a: Vec<u8>, b: () },
+    }
+
+    #[derive(Serialize, Debug)]
+    struct Struct {
+        unit: Unit,
+        newtype: Newtype,
+        tuple: Tuple,
+        pair: (i8, u128),
+        enums: Vec<Enum>,
+        map: BTreeMap<&'static str, Option<Newtype>>,
+    }
+
+    let value = Struct {
+        unit: Unit,
+        newtype: Newtype(1),
+        tuple: Tuple(-1, "x\"y"),
+        pair: (0, u128::MAX),
+        enums: vec![
+            Enum::Unit,
+            Enum::Newtype(Some('c')),
+            Enum::Newtype(None),
+            Enum::Tuple(1.5, true),
+            Enum::Struct { a: vec![], b: () },
+        ],
+        map: BTreeMap::from([("k", Some(Newtype(2))), ("v", None)]),
+    };
+    assert_eq!(
+        format!("{:?}", serde_stacker::debug(&value)),
+        format!("{:?}", value),
+    );
+    assert_eq!(
+        format!("{:#?}", serde_stacker::debug(&value)),
+        format!("{:#?}", value),
+    );
+
+    let mut value = Value::Null;
+    for _ in 0..10000 {
+        value = Value::Array(vec![value]);
+    }
+    let debug = format!("{:?}", serde_stacker::debug(&value));
+    drop_carefully(value);
+    assert_eq!(
+        debug,
+        format!("{}(){}", "[".repeat(10000), "]".repeat(10000))
+    );
+
+    let value = vec![vec![(1,)], vec![], vec![(2,), (3,)]];
+    assert_eq!(
+        format!("{:?}", serde_stacker::debug(&value)),
+        format!("{:?}", value),
+    );
+    assert_eq!(
+        format!("{:#?}", serde_stacker::debug(&value)),
+        format!("{:#?}", value),
+    );
+
+    // The alternate form is quadratic in size, so count the output instead of
+    // collecting it.
+    let depth = 5000;
+    let mut value = Value::Null;
+    for _ in 0..depth {
+        value = Value::Array(vec![value]);
+    }
+    let mut len = Len(0);
+    write!(len, "{:#?}", serde_stacker::debug(&value)).unwrap();
+    drop_carefully(value);
+    // Per level, "[\n" and ",\n" and "]" plus the indentation on both lines.
+    assert_eq!(len.0, 4 * depth * depth + 5 * depth + "()".len());
+}
+
+struct Len(usize);
+
+impl fmt::Write for Len {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_eq_hash() {
+    fn hash<T: ?Sized + Serialize>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        serde_stacker::hash(value, &mut hasher).unwrap();
+        hasher.finish()
+    }
+
+    #[derive(Serialize)]
+    enum Enum {
+        A(u8),
+        B(u8),
+    }
+
+    struct Fail;
+
+    impl Serialize for Fail {
+        fn serialize<S: serde::Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("oh no"))
+        }
+    }
+
+    assert!(serde_stacker::eq(&vec![1, 2], &[1, 2][..]).unwrap());
+    assert!(!serde_stacker::eq(&vec![1, 2], &vec![1, 2, 3]).unwrap());
+    assert!(!serde_stacker::eq(&vec![1, 2, 3], &vec![1, 2]).unwrap());
+    assert!(serde_stacker::eq(&[1, 2], &(1, 2)).unwrap());
+    assert!(!serde_stacker::eq(&vec![1, 2], &(1, 2)).unwrap());
+    assert!(!serde_stacker::eq(&Enum::A(1), &Enum::B(1)).unwrap());
+    assert!(!serde_stacker::eq(&f64::NAN, &f64::NAN).unwrap());
+    assert!(serde_stacker::eq(&0.0, &-0.0).unwrap());
+    assert_eq!(hash(&0.0), hash(&-0.0));
+
+    let error = serde_stacker::eq(&(1, 2), &(1, Fail)).unwrap_err();
+    assert_eq!(error.to_string(), "oh no");
+    assert!(serde_stacker::eq(&Fail, &1).is_err());
+    assert!(serde_stacker::hash(&Fail, &mut DefaultHasher::new()).is_err());
+    assert_eq!(hash(&vec![1, 2]), hash(&[1, 2][..]));
+    assert_ne!(hash(&Enum::A(1)), hash(&Enum::B(1)));
+    assert_ne!(hash(&(vec![1], vec![2])), hash(&(vec![1, 2], vec![0; 0])));
+
+    let mut a = Value::Null;
+    let mut b = Value::Null;
+    for i in 0..10000 {
+        a = Value::Array(vec![a, Value::from(i)]);
+        b = Value::Array(vec![b, Value::from(i)]);
+    }
+    let equal = serde_stacker::eq(&a, &b).unwrap();
+    let hashes = (hash(&a), hash(&b));
+    b = Value::Array(vec![b]);
+    let unequal = serde_stacker::eq(&a, &b).unwrap();
+    drop_carefully(a);
+    drop_carefully(b);
+    assert!(equal);
+    assert_eq!(hashes.0, hashes.1);
+    assert!(!unequal);
+}
+
+#[test]
+fn test_deep_clone() {
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Newtype(u8);
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Enum {
+        Unit,
+        Newtype(Option<char>),
+        Tuple(f64, bool),
+        Struct { a: Vec<u8>, b: () },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(untagged)]
+    enum Untagged {
+        Enum(Enum),
+        Other(String),
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Struct {
+        newtype: Newtype,
+        pair: (i8, u128),
+        enums: Vec<Enum>,
+        untagged: Vec<Untagged>,
+        map: BTreeMap<String, Option<Newtype>>,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        skipped: Option<u8>,
+        json: Value,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Asymmetric {
+        #[serde(skip_serializing)]
+        field: u8,
+    }
+
+    let value = Struct {
+        newtype: Newtype(1),
+        pair: (0, u128::MAX),
+        enums: vec![
+            Enum::Unit,
+            Enum::Newtype(Some('c')),
+            Enum::Newtype(None),
+            Enum::Tuple(1.5, true),
+            Enum::Struct { a: vec![], b: () },
+        ],
+        untagged: vec![
+            Untagged::Enum(Enum::Tuple(0.0, false)),
+            Untagged::Other("x".to_owned()),
+        ],
+        map: BTreeMap::from([("k".to_owned(), Some(Newtype(2))), ("v".to_owned(), None)]),
+        skipped: None,
+        json: serde_json::json!({"a": [1, null, "b"]}),
+    };
+    assert_eq!(serde_stacker::deep_clone(&value).unwrap(), value);
+
+    let error = serde_stacker::deep_clone(&Asymmetric { field: 0 })
+        .map(|clone| clone.field)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "missing field `field`");
+
+    let mut value = Value::Null;
+    for _ in 0..10000 {
+        value = Value::Array(vec![value]);
+    }
+    let clone = serde_stacker::deep_clone(&value).unwrap();
+    let equal = serde_stacker::eq(&value, &clone).unwrap();
+    drop_carefully(value);
+    drop_carefully(clone);
+    assert!(equal);
+}
+
+#[test]
+fn test_config() {
+    let config = Config::builder().stack_size(100_000).build().unwrap();
+    assert!(config.stack_size() >= 100_000);
+    assert_eq!(config.stack_size() % 4096, 0);
+    assert!(config.stack_size() < 100_000 + 64 * 1024);
+
+    let error = Config::builder()
+        .red_zone(1024 * 1024)
+        .stack_size(1024 * 1024)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "red_zone (1048576 bytes) must be smaller than stack_size (1048576 bytes)",
+    );
+}
+
 fn drop_carefully(value: Value) {
     let mut stack = vec![value];
     while let Some(value) = stack.pop() {
@@ -44,3 +866,111 @@ fn drop_carefully(value: Value) {
         }
     }
 }
+
+#[test]
+fn test_stacked() {
+    #[derive(Serialize, Deserialize)]
+    struct Message {
+        id: u64,
+        payload: Stacked<Value>,
+    }
+
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+    json = format!("{{\"id\":1,\"payload\":{}}}", json);
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let message = Message::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    let result = message.serialize(&mut serializer);
+
+    drop_carefully(message.payload.into_inner());
+
+    result.unwrap();
+    assert_eq!(out, json.as_bytes());
+}
+
+#[test]
+fn test_stacked_seed() {
+    // Interns the strings found in nested arrays, and counts them.
+    struct Intern<'a> {
+        strings: &'a mut Vec<String>,
+        count: &'a mut usize,
+    }
+
+    impl<'de> DeserializeSeed<'de> for Intern<'_> {
+        type Value = ();
+
+        fn deserialize<D: serde::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for Intern<'_> {
+        type Value = ();
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("strings in nested arrays")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<(), E> {
+            if !self.strings.iter().any(|string| string == v) {
+                self.strings.push(v.to_owned());
+            }
+            *self.count += 1;
+            Ok(())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+            while let Some(()) = seq.next_element_seed(Intern {
+                strings: self.strings,
+                count: self.count,
+            })? {}
+            Ok(())
+        }
+    }
+
+    let mut json = String::new();
+    for i in 0..10000 {
+        json.push_str(if i % 2 == 0 { "[\"a\"," } else { "[\"b\"," });
+    }
+    json.push_str("[]");
+    json.push_str(&"]".repeat(10000));
+
+    let mut strings = Vec::new();
+    let mut count = 0;
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let seed = Intern {
+        strings: &mut strings,
+        count: &mut count,
+    };
+    StackedSeed::new(seed)
+        .deserialize(&mut deserializer)
+        .unwrap();
+    assert_eq!(strings, ["a", "b"]);
+    assert_eq!(count, 10000);
+
+    let config = Config::builder().max_depth(100).build().unwrap();
+    let mut strings = Vec::new();
+    let mut count = 0;
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let seed = Intern {
+        strings: &mut strings,
+        count: &mut count,
+    };
+    let error = StackedSeed::with_config(seed, config)
+        .deserialize(&mut deserializer)
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 100"));
+    assert_eq!(strings, ["a", "b"]);
+    assert_eq!(count, 100);
+}
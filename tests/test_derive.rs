@@ -0,0 +1,122 @@
+#![cfg(feature = "derive")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde::{Deserialize, Serialize};
+use serde_stacker::StackSafe;
+use std::collections::BTreeMap;
+
+#[derive(StackSafe, Debug, PartialEq)]
+enum Expr {
+    Lit(i64),
+    Neg(Box<Expr>),
+    Add(Box<Self>, Box<Self>),
+    #[serde(rename = "call")]
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+}
+
+#[derive(StackSafe)]
+struct Tree<T> {
+    value: T,
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    children: Vec<Tree<T>>,
+}
+
+// Recursion through a type which does not derive StackSafe itself.
+#[derive(StackSafe)]
+struct Outer {
+    #[stack_safe]
+    inner: Option<Box<Inner>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Inner {
+    outer: Outer,
+}
+
+#[test]
+fn test_enum() {
+    let mut json = "{\"Lit\":1}".to_owned();
+    for i in 0..10000 {
+        json = if i % 2 == 0 {
+            format!("{{\"Neg\":{}}}", json)
+        } else {
+            format!("{{\"call\":{{\"name\":\"f\",\"args\":[{}]}}}}", json)
+        };
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let expr = Expr::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    expr.serialize(&mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    assert_eq!(out, json.as_bytes());
+
+    let mut stack = vec![expr];
+    while let Some(expr) = stack.pop() {
+        match expr {
+            Expr::Lit(_) => {}
+            Expr::Neg(e) => stack.push(*e),
+            Expr::Add(a, b) => stack.extend([*a, *b]),
+            Expr::Call { args, .. } => stack.extend(args),
+        }
+    }
+
+    let expr: Expr = serde_json::from_str(r#"{"Add":[{"Lit":1},{"Lit":2}]}"#).unwrap();
+    assert_eq!(
+        expr,
+        Expr::Add(Box::new(Expr::Lit(1)), Box::new(Expr::Lit(2))),
+    );
+}
+
+#[test]
+fn test_generic() {
+    let mut json = "{\"value\":\"leaf\"}".to_owned();
+    for _ in 0..10000 {
+        json = format!("{{\"value\":\"node\",\"children\":[{}]}}", json);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let tree = Tree::<String>::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    tree.serialize(&mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    assert_eq!(out, json.as_bytes());
+
+    let mut stack = vec![tree];
+    while let Some(mut tree) = stack.pop() {
+        stack.append(&mut tree.children);
+    }
+
+    let tree: Tree<BTreeMap<String, u8>> = serde_json::from_str(r#"{"value":{"a":1}}"#).unwrap();
+    assert_eq!(tree.value["a"], 1);
+}
+
+#[test]
+fn test_mutual_recursion() {
+    let mut json = "{\"inner\":null}".to_owned();
+    for _ in 0..10000 {
+        json = format!("{{\"inner\":{{\"outer\":{}}}}}", json);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let outer = Outer::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    outer
+        .serialize(&mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    assert_eq!(out, json.as_bytes());
+
+    let mut next = outer.inner;
+    while let Some(inner) = next {
+        next = inner.outer.inner;
+    }
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "std")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    #[serde(with = "serde_stacker::field")]
+    children: Vec<Node>,
+}
+
+#[test]
+fn test_field() {
+    let mut json = "{\"children\":[]}".to_owned();
+    for _ in 0..10000 {
+        json = format!("{{\"children\":[{}]}}", json);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let node = Node::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut out);
+    node.serialize(&mut serializer).unwrap();
+    assert_eq!(out, json.as_bytes());
+
+    drop_carefully(node);
+}
+
+fn drop_carefully(node: Node) {
+    let mut stack = vec![node];
+    while let Some(mut node) = stack.pop() {
+        stack.append(&mut node.children);
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "serde_json")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde_json::{Map, Value};
+use serde_stacker::json::{self, SafeValue};
+use serde_stacker::Config;
+
+#[test]
+fn test_drop_value() {
+    let mut value = Value::Null;
+    for i in 0..100_000 {
+        value = if i % 2 == 0 {
+            Value::Array(vec![value, Value::Bool(true)])
+        } else {
+            let mut object = Map::new();
+            object.insert("k".to_owned(), value);
+            Value::Object(object)
+        };
+    }
+
+    json::drop_value(value);
+}
+
+#[test]
+fn test_safe_value() {
+    let mut value = Value::Null;
+    for _ in 0..100_000 {
+        value = Value::Array(vec![value]);
+    }
+
+    let value = SafeValue::new(value);
+    let serialized = serde_json::to_string(&value).unwrap();
+    assert_eq!(serialized.len(), 100_000 + "null".len() + 100_000);
+    drop(value);
+
+    let mut deserializer = serde_json::Deserializer::from_str(&serialized);
+    deserializer.disable_recursion_limit();
+    let value: SafeValue = serde::Deserialize::deserialize(&mut deserializer).unwrap();
+    assert!(value.is_array());
+}
+
+#[test]
+fn test_from_str() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let value: SafeValue = json::from_str(&json).unwrap();
+    assert!(value.is_array());
+
+    let value: SafeValue = json::from_slice(json.as_bytes()).unwrap();
+    assert!(value.is_array());
+
+    let value: SafeValue = json::from_reader(json.as_bytes()).unwrap();
+    assert!(value.is_array());
+
+    let error = json::from_str::<SafeValue>("[] []").err().unwrap();
+    assert_eq!(error.to_string(), "trailing characters at line 1 column 4");
+
+    let config = Config::builder().max_depth(100).build().unwrap();
+    let error = json::from_str_with_config::<SafeValue>(&json, config)
+        .err()
+        .unwrap();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 100"));
+}
+
+#[test]
+fn test_to_string() {
+    let mut value = Value::Null;
+    for _ in 0..10000 {
+        value = Value::Array(vec![value]);
+    }
+    let value = SafeValue::new(value);
+
+    let string = json::to_string(&*value).unwrap();
+    assert_eq!(string.len(), 10000 + "null".len() + 10000);
+
+    let vec = json::to_vec(&*value).unwrap();
+    assert_eq!(vec, string.as_bytes());
+
+    let mut writer = Vec::new();
+    json::to_writer(&mut writer, &*value).unwrap();
+    assert_eq!(writer, string.as_bytes());
+
+    // Pretty output is quadratic in the depth because of indentation.
+    let mut value = Value::Null;
+    for _ in 0..1000 {
+        value = Value::Array(vec![value]);
+    }
+    let value = SafeValue::new(value);
+
+    let pretty = json::to_string_pretty(&*value).unwrap();
+    assert!(pretty.starts_with("[\n  [\n    [\n"));
+    assert_eq!(json::to_vec_pretty(&*value).unwrap(), pretty.as_bytes());
+
+    let mut writer = Vec::new();
+    json::to_writer_pretty(&mut writer, &*value).unwrap();
+    assert_eq!(writer, pretty.as_bytes());
+}
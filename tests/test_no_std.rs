@@ -0,0 +1,42 @@
+#![cfg(not(feature = "std"))]
+
+use serde::Deserialize;
+use serde_json::Value;
+use serde_stacker::Config;
+
+#[test]
+fn test_default_max_depth() {
+    assert_eq!(Config::default().max_depth(), Some(128));
+
+    let json = "[".repeat(128) + &"]".repeat(128);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+    Value::deserialize(deserializer).unwrap();
+
+    let json = "[".repeat(129) + &"]".repeat(129);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+    let error = Value::deserialize(deserializer).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 128"));
+}
+
+#[test]
+fn test_debug_max_depth() {
+    let mut value = Value::Null;
+    for _ in 0..129 {
+        value = Value::Array(vec![value]);
+    }
+    let debug = format!("{:?}", serde_stacker::debug(&value));
+    assert_eq!(
+        debug,
+        format!(
+            "{}<error: exceeded maximum nesting depth of 128>{}",
+            "[".repeat(128),
+            "]".repeat(128),
+        ),
+    );
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "std")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde_json::Value;
+use serde_stacker::pool::StackPool;
+use serde_stacker::Config;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+#[test]
+fn test_deserialize_serialize() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let pool = StackPool::new(2, Config::default()).unwrap();
+
+    let text = json.clone();
+    let value: Value = pool
+        .deserialize(move |input| {
+            let mut deserializer = serde_json::Deserializer::from_str(&text);
+            deserializer.disable_recursion_limit();
+            input.deserialize(&mut deserializer)
+        })
+        .unwrap();
+
+    // Serializing moves the value to the pool, which also takes care of
+    // dropping it on a big stack.
+    let out = pool
+        .serialize(value, |output| {
+            let mut out = Vec::new();
+            output.serialize(&mut serde_json::Serializer::new(&mut out))?;
+            Ok::<_, serde_json::Error>(out)
+        })
+        .unwrap();
+    assert_eq!(out, json.as_bytes());
+}
+
+#[test]
+fn test_errors() {
+    let config = Config::builder().max_depth(3).build().unwrap();
+    let pool = StackPool::new(1, config).unwrap();
+
+    let error = pool
+        .deserialize::<Value, _>(|input| {
+            let mut deserializer = serde_json::Deserializer::from_str("[[[[]]]]");
+            input.deserialize(&mut deserializer)
+        })
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("exceeded maximum nesting depth of 3"));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        pool.deserialize::<Value, serde_json::Error>(|_input| panic!("oh no"))
+    }));
+    let payload = result.unwrap_err();
+    assert_eq!(*payload.downcast::<&str>().unwrap(), "oh no");
+
+    // The thread survives a panic and keeps accepting work.
+    let value: Value = pool
+        .deserialize(|input| {
+            let mut deserializer = serde_json::Deserializer::from_str("[[]]");
+            input.deserialize(&mut deserializer)
+        })
+        .unwrap();
+    assert_eq!(value.to_string(), "[[]]");
+}
+
+#[test]
+fn test_async() {
+    let pool = StackPool::new(4, Config::default()).unwrap();
+
+    let tasks: Vec<_> = (0..16)
+        .map(|i| {
+            pool.deserialize_async(move |input| {
+                let json = format!("[{}]", i);
+                let mut deserializer = serde_json::Deserializer::from_str(&json);
+                input.deserialize(&mut deserializer)
+            })
+        })
+        .collect();
+
+    for (i, task) in tasks.into_iter().enumerate() {
+        let value: Vec<usize> = block_on(task).unwrap();
+        assert_eq!(value, [i]);
+    }
+
+    let task = pool.serialize_async(vec![1, 2, 3], |output| {
+        serde_json::to_string(output.value())
+    });
+    assert_eq!(block_on(task).unwrap(), "[1,2,3]");
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "testing")]
+
+use serde::{Deserialize, Serialize};
+use serde_stacker::json::SafeValue;
+use serde_stacker::testing::{self, Shape};
+
+#[derive(Serialize, Deserialize)]
+struct Node {
+    next: Option<Box<Node>>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Alternating {
+    A(Box<Alternating>),
+    B(Box<Alternating>),
+    Leaf,
+}
+
+fn shapes(depth: usize) -> [Shape; 5] {
+    [
+        testing::deep_array(depth),
+        testing::deep_object(depth),
+        testing::deep_option_chain(depth),
+        testing::alternating_enum(depth),
+        testing::wide_and_deep(3, depth),
+    ]
+}
+
+#[test]
+fn test_to_json() {
+    let json = shapes(3).map(|shape| shape.to_json());
+    assert_eq!(
+        json,
+        [
+            "[[[null]]]",
+            r#"{"a":{"a":{"a":null}}}"#,
+            r#"{"next":{"next":{"next":null}}}"#,
+            r#"{"A":{"B":{"A":"Leaf"}}}"#,
+            "[0,1,[0,1,[0,1,null]]]",
+        ],
+    );
+    assert_eq!(testing::deep_array(0).to_json(), "null");
+    assert_eq!(testing::wide_and_deep(1, 2).to_json(), "[[null]]");
+}
+
+#[test]
+fn test_round_trip() {
+    for shape in shapes(10000) {
+        let _: SafeValue = testing::assert_round_trip(&shape);
+    }
+
+    let chain: Option<Box<Node>> = testing::assert_round_trip(&testing::deep_option_chain(10000));
+    let mut next = chain;
+    while let Some(node) = next {
+        next = node.next;
+    }
+
+    let alternating: Alternating = testing::assert_round_trip(&testing::alternating_enum(10000));
+    let mut next = alternating;
+    while let Alternating::A(inner) | Alternating::B(inner) = next {
+        next = *inner;
+    }
+}
+
+#[test]
+#[should_panic(expected = "produced different JSON")]
+fn test_round_trip_mismatch() {
+    let _: Vec<Option<f64>> = testing::assert_round_trip(&testing::wide_and_deep(2, 1));
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "tracing")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde::Deserialize;
+use serde_json::Value;
+use serde_stacker::{Config, Stacked};
+use std::fmt::{self, Debug, Write as _};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+#[derive(Clone, Default)]
+struct Collect {
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+struct Format<'a>(&'a mut String);
+
+impl Visit for Format<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl Subscriber for Collect {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == "serde_stacker"
+    }
+
+    fn new_span(&self, _span: &Attributes) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event) {
+        let mut fields = String::new();
+        event.record(&mut Format(&mut fields));
+        let line = format!("{} {}", event.metadata().level(), fields);
+        self.events.lock().unwrap().push(line);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+impl Debug for Collect {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("Collect")
+    }
+}
+
+#[test]
+fn test_summary() {
+    let collect = Collect::default();
+    tracing::subscriber::with_default(collect.clone(), || {
+        let mut deserializer = serde_json::Deserializer::from_str("[[1], [[2]]]");
+        let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+        Value::deserialize(deserializer).unwrap();
+    });
+
+    let events = collect.events.lock().unwrap();
+    assert_eq!(
+        *events,
+        ["DEBUG finished operation=\"deserialize\" max_depth=3 segments_allocated=0 segments_reused=0 peak_stack_bytes=0"],
+    );
+}
+
+#[test]
+fn test_summary_nested() {
+    let collect = Collect::default();
+    tracing::subscriber::with_default(collect.clone(), || {
+        let json = r#"[[1], {"a": [2]}, null]"#;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+        let values = Vec::<serde_stacker::Value>::deserialize(deserializer).unwrap();
+        assert_eq!(values.len(), 3);
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let deserializer = serde_stacker::Deserializer::new(&mut deserializer);
+        let values = Vec::<Stacked<Value>>::deserialize(deserializer).unwrap();
+        assert_eq!(values.len(), 3);
+    });
+
+    let events = collect.events.lock().unwrap();
+    assert_eq!(
+        *events,
+        [
+            "DEBUG finished operation=\"deserialize\" max_depth=3 segments_allocated=0 segments_reused=0 peak_stack_bytes=0",
+            "DEBUG finished operation=\"deserialize\" max_depth=3 segments_allocated=0 segments_reused=0 peak_stack_bytes=0",
+        ],
+    );
+}
+
+#[test]
+fn test_segment_allocated() {
+    let mut json = String::new();
+    for _ in 0..10000 {
+        json = format!("[{}]", json);
+    }
+
+    let config = Config::builder().stack_size(1024 * 1024).build().unwrap();
+
+    let collect = Collect::default();
+    let value = tracing::subscriber::with_default(collect.clone(), || {
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        deserializer.disable_recursion_limit();
+        let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+        Value::deserialize(deserializer).unwrap()
+    });
+
+    let events = collect.events.lock().unwrap();
+    let allocated = events
+        .iter()
+        .filter(|event| event.starts_with("DEBUG allocating stack segment "))
+        .count();
+    assert!(allocated > 1);
+    let summary = events.last().unwrap();
+    assert!(summary.starts_with("DEBUG finished operation=\"deserialize\" max_depth=10000 "));
+    assert!(summary.contains(&format!(" segments_allocated={} ", allocated)));
+    drop(events);
+
+    drop_carefully(value);
+}
+
+#[test]
+fn test_limit_exceeded() {
+    let config = Config::builder().max_depth(1).build().unwrap();
+
+    let collect = Collect::default();
+    tracing::subscriber::with_default(collect.clone(), || {
+        let mut deserializer = serde_json::Deserializer::from_str("[[]]");
+        let deserializer = serde_stacker::Deserializer::with_config(&mut deserializer, config);
+        Value::deserialize(deserializer).unwrap_err();
+    });
+
+    let events = collect.events.lock().unwrap();
+    assert_eq!(
+        events[0],
+        "WARN exceeded maximum nesting depth depth=1 max_depth=1",
+    );
+}
+
+fn drop_carefully(value: Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        if let Value::Array(array) = value {
+            stack.extend(array);
+        }
+    }
+}
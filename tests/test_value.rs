@@ -0,0 +1,124 @@
+#![cfg(feature = "std")]
+#![allow(clippy::uninlined_format_args)]
+
+use serde::{Deserialize, Serialize};
+use serde_stacker::Value;
+
+fn deep_json() -> String {
+    let mut json = String::new();
+    for _ in 0..50000 {
+        json.push_str("{\"k\":[1,");
+    }
+    json.push_str("null");
+    for _ in 0..50000 {
+        json.push_str("]}");
+    }
+    json
+}
+
+#[test]
+fn test_round_trip() {
+    let json = deep_json();
+
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let value = Value::deserialize(&mut deserializer).unwrap();
+
+    let mut out = Vec::new();
+    value
+        .serialize(&mut serde_json::Serializer::new(&mut out))
+        .unwrap();
+    assert_eq!(out, json.as_bytes());
+}
+
+#[test]
+fn test_clone_eq() {
+    let json = deep_json();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let value = Value::deserialize(&mut deserializer).unwrap();
+
+    let mut copy = value.clone();
+    assert!(value == copy);
+
+    let mut innermost = &mut copy;
+    loop {
+        innermost = match innermost {
+            Value::Seq(seq) => &mut seq[1],
+            Value::Map(map) => &mut map[0].1,
+            _ => break,
+        };
+    }
+    *innermost = Value::Bool(false);
+    assert!(value != copy);
+
+    assert_ne!(Value::F64(f64::NAN), Value::F64(f64::NAN));
+    assert_ne!(Value::I64(1), Value::U64(1));
+    assert_ne!(
+        Value::Seq(vec![Value::Seq(vec![])]),
+        Value::Seq(vec![Value::Seq(vec![]), Value::Null]),
+    );
+}
+
+#[test]
+fn test_debug() {
+    // Same shape as Value, with a derived Debug impl.
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum Derived {
+        Null,
+        Bool(bool),
+        I64(i64),
+        U64(u64),
+        F64(f64),
+        String(String),
+        Bytes(Vec<u8>),
+        Seq(Vec<Derived>),
+        Map(Map),
+    }
+
+    struct Map(Vec<(Derived, Derived)>);
+
+    impl std::fmt::Debug for Map {
+        fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter
+                .debug_map()
+                .entries(self.0.iter().map(|(k, v)| (k, v)))
+                .finish()
+        }
+    }
+
+    let value = Value::Seq(vec![
+        Value::Null,
+        Value::Map(vec![
+            (Value::String("k".to_owned()), Value::Seq(vec![])),
+            (Value::I64(-1), Value::Map(vec![])),
+        ]),
+        Value::Seq(vec![Value::Bool(true), Value::U64(1), Value::F64(1.5)]),
+        Value::Bytes(vec![1, 2]),
+    ]);
+    let derived = Derived::Seq(vec![
+        Derived::Null,
+        Derived::Map(Map(vec![
+            (Derived::String("k".to_owned()), Derived::Seq(vec![])),
+            (Derived::I64(-1), Derived::Map(Map(vec![]))),
+        ])),
+        Derived::Seq(vec![
+            Derived::Bool(true),
+            Derived::U64(1),
+            Derived::F64(1.5),
+        ]),
+        Derived::Bytes(vec![1, 2]),
+    ]);
+    assert_eq!(format!("{:?}", value), format!("{:?}", derived));
+    assert_eq!(format!("{:#?}", value), format!("{:#?}", derived));
+
+    let json = deep_json();
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let value = Value::deserialize(&mut deserializer).unwrap();
+    let debug = format!("{:?}", value);
+    assert!(debug.starts_with("Map({String(\"k\"): Seq([U64(1), Map({"));
+    assert!(debug.contains("Map({String(\"k\"): Seq([U64(1), Null])})"));
+    assert!(debug.ends_with("])})])})"));
+}